@@ -0,0 +1,1046 @@
+//! Silence, clear and list flows shared by every monitoring backend
+
+use std::time::{SystemTime,UNIX_EPOCH};
+
+use regex::{Regex,RegexBuilder};
+
+use backend::{PlannedRequest,SilenceBackend,Validated};
+use err::SensuError;
+use export::Export;
+use opts::{ClearOpts,ExtendOpts,Extension,ListFilter,ListOpts,ListSort,QueryOpts,SilenceOpts,
+           TargetType};
+use resources::{ShushResources,ShushResourceType};
+use sensu::{Expire,SensuPayload,Silence};
+
+/// Outcome of a silence or clear action
+#[derive(Debug,Default)]
+pub struct Report {
+    /// Payloads that were successfully sent to the backend
+    pub payloads: Vec<SensuPayload>,
+    /// Messages about targets or checks that were dropped during validation
+    pub warnings: Vec<String>,
+}
+
+/// Error of an action that failed after some of its requests may have been sent
+#[derive(Debug)]
+pub struct Interrupted<T = Report> {
+    /// What the action did before the failure
+    pub report: T,
+    /// Error that stopped the action
+    pub error: SensuError,
+}
+
+impl<T> From<Interrupted<T>> for SensuError {
+    fn from(i: Interrupted<T>) -> Self {
+        i.error
+    }
+}
+
+/// Request that would be sent for a single payload
+#[derive(Debug)]
+pub struct PlannedSilence {
+    /// Payload the request was built from
+    pub payload: SensuPayload,
+    /// Method, endpoint and body that would be sent
+    pub request: PlannedRequest,
+    /// Number of clients a subscription-level or global entry would cover - `None` for entries
+    /// on a single client or if clients could not be fetched
+    pub clients: Option<usize>,
+}
+
+/// Outcome of a dry run of a silence or clear action
+#[derive(Debug,Default)]
+pub struct Plan {
+    /// Requests that would be sent to the backend
+    pub silences: Vec<PlannedSilence>,
+    /// Messages about targets or checks that were dropped during validation
+    pub warnings: Vec<String>,
+}
+
+/// Silence entries that currently suppress a check on a client
+#[derive(Clone,Debug,PartialEq,Serialize)]
+pub struct Coverage {
+    /// Client name
+    pub client: String,
+    /// Check name
+    pub check: String,
+    /// Whether any entry suppresses the check
+    pub silenced: bool,
+    /// Entries that suppress the check
+    pub silences: Vec<Silence>,
+}
+
+/// Changes needed to bring the silences on the targets of a manifest in line with it
+#[derive(Debug,Default)]
+pub struct ManifestDiff {
+    /// Entries in the manifest that do not exist yet
+    pub create: Vec<PlannedSilence>,
+    /// Entries that differ from the manifest and are reposted under the same ID
+    pub update: Vec<PlannedSilence>,
+    /// Entries on targets of the manifest that it does not contain
+    pub clear: Vec<PlannedSilence>,
+    /// IDs of entries that already match the manifest
+    pub unchanged: Vec<String>,
+    /// Messages about targets or checks that were dropped during validation
+    pub warnings: Vec<String>,
+}
+
+/// Progress of `apply` when one of its requests failed
+#[derive(Debug,Default)]
+pub struct PartialApply {
+    /// Changes made before the failure, along with the unchanged entries and warnings
+    pub applied: ManifestDiff,
+    /// Change whose request failed - `None` if the manifest could not be compared at all
+    pub failed: Option<PlannedSilence>,
+    /// Changes not attempted because of the failure
+    pub pending: ManifestDiff,
+}
+
+/// Seconds the begin and expiry times of an entry may differ from a manifest before the entry
+/// counts as changed
+const DRIFT_TOLERANCE: i64 = 60;
+
+fn unix_now() -> i64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or(0)
+}
+
+/// Resolve targets and checks into the payloads to send to the backend, copying every other
+/// field from `template`
+fn payloads<B>(backend: &mut B, resources: Option<ShushResources>,
+               checks: Option<Vec<String>>, template: SensuPayload, warnings: &mut Vec<String>)
+               -> Result<Vec<SensuPayload>, SensuError> where B: SilenceBackend {
+    let resources = match resources {
+        Some(res) => {
+            let validated = backend.resolve_targets(res)?;
+            warnings.extend(validated.warnings);
+            Some(validated.items)
+        },
+        None => None,
+    };
+    let payload = |res, chk| SensuPayload { res, chk, ..template.clone() };
+    match (resources, checks) {
+        (Some(res), Some(chk)) => Ok(iproduct!(res, chk).map(|(r, c)| payload(Some(r), Some(c)))
+                                     .collect()),
+        (Some(res), None) => Ok(res.into_iter().map(|r| payload(Some(r), None)).collect()),
+        (None, Some(chk)) => Ok(chk.into_iter().map(|c| payload(None, Some(c))).collect()),
+        (None, None) => Err(SensuError::new("No targets specified")),
+    }
+}
+
+fn silence_payloads<B>(backend: &mut B, s: SilenceOpts, warnings: &mut Vec<String>)
+                      -> Result<Vec<SensuPayload>, SensuError> where B: SilenceBackend {
+    let checks = s.checks.map(|cks| {
+        let validated = backend.validate_checks(cks);
+        warnings.extend(validated.warnings);
+        validated.items
+    });
+    let template = SensuPayload {
+        res: None,
+        chk: None,
+        expire: Some(s.expire),
+        reason: s.reason,
+        creator: None,
+        begin: s.begin,
+        id: None,
+    };
+    payloads(backend, s.resources, checks, template, warnings)
+}
+
+/// Whether entries for these targets can cover more than one client
+fn subscription_level(resources: &Option<ShushResources>) -> bool {
+    resources.as_ref().map(|r| r.res_type == ShushResourceType::Sub).unwrap_or(true)
+}
+
+/// Build the requests for `payloads` and count the clients each one covers
+fn plan<B, F>(backend: &mut B, payloads: Vec<SensuPayload>, mut warnings: Vec<String>,
+              subscription_level: bool, request: F) -> Result<Plan, SensuError>
+              where B: SilenceBackend, F: Fn(&B, &SensuPayload) -> Result<PlannedRequest, SensuError> {
+    let clients = if subscription_level {
+        match backend.clients() {
+            Ok(clients) => Some(clients),
+            Err(e) => {
+                warnings.push(format!("Failed to fetch clients - skipping coverage: {}", e));
+                None
+            },
+        }
+    } else {
+        None
+    };
+    let mut plan = Plan { silences: Vec::new(), warnings };
+    for payload in payloads {
+        let covered = clients.as_ref().map(|cs| match payload.res {
+            Some(ref sub) => cs.iter().filter(|c| c.subscriptions.contains(sub)).count(),
+            None => cs.len(),
+        });
+        plan.silences.push(PlannedSilence {
+            request: request(backend, &payload)?,
+            payload,
+            clients: covered,
+        });
+    }
+    Ok(plan)
+}
+
+/// Silence the checks and targets in `s`. If a request fails, the error carries the report
+/// of the silences that were created before it.
+pub fn silence<B>(backend: &mut B, s: SilenceOpts) -> Result<Report, Interrupted>
+        where B: SilenceBackend {
+    let mut report = Report::default();
+    let payloads = match silence_payloads(backend, s, &mut report.warnings) {
+        Ok(payloads) => payloads,
+        Err(error) => return Err(Interrupted { report, error }),
+    };
+    for payload in payloads {
+        if let Err(error) = backend.create_silence(payload.clone()) {
+            return Err(Interrupted { report, error });
+        }
+        report.payloads.push(payload);
+    }
+    Ok(report)
+}
+
+/// Resolve and validate everything `silence` would, but only report the requests it would send
+pub fn plan_silence<B>(backend: &mut B, s: SilenceOpts) -> Result<Plan, SensuError>
+        where B: SilenceBackend {
+    let mut warnings = Vec::new();
+    let subscription_level = subscription_level(&s.resources);
+    let payloads = silence_payloads(backend, s, &mut warnings)?;
+    plan(backend, payloads, warnings, subscription_level, B::plan_create)
+}
+
+fn clear_payloads<B>(backend: &mut B, s: ClearOpts, warnings: &mut Vec<String>)
+                     -> Result<Vec<SensuPayload>, SensuError> where B: SilenceBackend {
+    match s.ids {
+        Some(ids) => {
+            let existing: Vec<String> = backend.silences()?.into_iter().map(|s| s.id).collect();
+            Ok(ids.into_iter().filter(|id| {
+                let exists = existing.contains(id);
+                if !exists {
+                    warnings.push(format!("Silence ID {} does not exist - filtering...", id));
+                }
+                exists
+            }).map(|id| SensuPayload { id: Some(id), ..SensuPayload::default() }).collect())
+        },
+        None => payloads(backend, s.resources, s.checks, SensuPayload::default(), warnings),
+    }
+}
+
+/// Clear silences on the checks and targets in `s`, or the entries with the IDs in `s`
+pub fn clear<B>(backend: &mut B, s: ClearOpts) -> Result<Report, SensuError>
+        where B: SilenceBackend {
+    let mut warnings = Vec::new();
+    let payloads = clear_payloads(backend, s, &mut warnings)?;
+    let mut report = Report { payloads: Vec::new(), warnings };
+    for payload in payloads {
+        backend.delete_silence(payload.clone())?;
+        report.payloads.push(payload);
+    }
+    Ok(report)
+}
+
+/// Resolve everything `clear` would, but only report the requests it would send
+pub fn plan_clear<B>(backend: &mut B, s: ClearOpts) -> Result<Plan, SensuError>
+        where B: SilenceBackend {
+    let mut warnings = Vec::new();
+    let subscription_level = s.ids.is_none() && subscription_level(&s.resources);
+    let payloads = clear_payloads(backend, s, &mut warnings)?;
+    plan(backend, payloads, warnings, subscription_level, B::plan_delete)
+}
+
+/// Whether an existing entry matches the payload closely enough to be left alone at the UNIX
+/// timestamp `now`
+fn up_to_date(existing: &Silence, payload: &SensuPayload, now: i64) -> bool {
+    let close = |a: i64, b: i64| (a - b).abs() <= DRIFT_TOLERANCE;
+    let begin = match (payload.begin, existing.begin) {
+        (Some(want), Some(have)) => close(want, have),
+        (Some(want), None) => want <= now,
+        (None, have) => have.map(|b| b <= now).unwrap_or(true),
+    };
+    // Durations are compared against the time the entry took effect so that applying the
+    // same manifest twice does not push every expiration back
+    let start = existing.begin.or(existing.timestamp);
+    let expires_at = existing.expires_at(now);
+    let (expiry, eor) = match payload.expire.clone().unwrap_or(Expire::NoExpiration(false)) {
+        Expire::NoExpiration(eor) => (expires_at.is_none(), eor),
+        Expire::Expire(secs, eor) => (match (expires_at, start) {
+            (Some(at), Some(start)) => close(at - start, secs as i64),
+            (Some(_), None) => true,
+            (None, _) => false,
+        }, eor),
+        Expire::Until(ts, eor) => (expires_at.map(|at| close(at, ts)).unwrap_or(false), eor),
+    };
+    begin && expiry && eor == existing.expire_on_resolve && payload.reason == existing.reason
+}
+
+fn extend_payloads<B>(backend: &mut B, e: ExtendOpts, warnings: &mut Vec<String>)
+                      -> Result<Vec<SensuPayload>, SensuError> where B: SilenceBackend {
+    let extension = e.extension;
+    let selected = match e.ids {
+        Some(ids) => {
+            let existing = backend.silences()?;
+            ids.into_iter().filter_map(|id| {
+                let found = existing.iter().find(|s| s.id == id).cloned();
+                if found.is_none() {
+                    warnings.push(format!("Silence ID {} does not exist - filtering...", id));
+                }
+                found
+            }).collect()
+        },
+        None => {
+            // A mutating command only touches the subscriptions and checks it names, so `web`
+            // does not extend `webhooks` as well
+            let anchor = |pattern: Option<String>| pattern.map(|p| format!("^(?:{})$", p));
+            let selection = ListOpts {
+                sub: anchor(e.selection.sub),
+                chk: anchor(e.selection.chk),
+                ..e.selection
+            };
+            let listed = list(backend, selection)?;
+            warnings.extend(listed.warnings);
+            listed.items
+        },
+    };
+
+    let now = unix_now();
+    Ok(selected.into_iter().filter_map(|s| {
+        let eor = s.expire_on_resolve;
+        let expire = match extension {
+            Extension::For(secs) => Expire::Expire(secs, eor),
+            Extension::Until(ts) => Expire::Until(ts, eor),
+            Extension::Never => Expire::NoExpiration(eor),
+            Extension::Add(secs) => match s.expires_at(now) {
+                Some(at) => match at.checked_add(secs as i64) {
+                    Some(until) => Expire::Until(until, eor),
+                    None => {
+                        warnings.push(format!("Silence {} cannot be extended that far - \
+                                               skipping...", s.id));
+                        return None;
+                    },
+                },
+                None => {
+                    warnings.push(format!("Silence {} never expires - skipping...", s.id));
+                    return None;
+                },
+            },
+        };
+        Some(SensuPayload {
+            res: s.subscription,
+            chk: s.check,
+            expire: Some(expire),
+            reason: s.reason,
+            creator: s.creator,
+            begin: s.begin.filter(|b| *b > now),
+            id: None,
+        })
+    }).collect())
+}
+
+/// Repost the silences selected by ID or by the listing filters in `e` with a new expiration,
+/// keeping their creator, reason and expire on resolve flag
+pub fn extend<B>(backend: &mut B, e: ExtendOpts) -> Result<Report, SensuError>
+        where B: SilenceBackend {
+    let mut warnings = Vec::new();
+    let payloads = extend_payloads(backend, e, &mut warnings)?;
+    let mut report = Report { payloads: Vec::new(), warnings };
+    for payload in payloads {
+        backend.create_silence(payload.clone())?;
+        report.payloads.push(payload);
+    }
+    Ok(report)
+}
+
+/// Select everything `extend` would, but only report the requests it would send
+pub fn plan_extend<B>(backend: &mut B, e: ExtendOpts) -> Result<Plan, SensuError>
+        where B: SilenceBackend {
+    let mut warnings = Vec::new();
+    let payloads = extend_payloads(backend, e, &mut warnings)?;
+    plan(backend, payloads, warnings, false, B::plan_create)
+}
+
+/// Repost silences created earlier so that durations count from now again
+pub fn renew<B>(backend: &mut B, payloads: &[SensuPayload]) -> Result<(), SensuError>
+        where B: SilenceBackend {
+    for payload in payloads {
+        backend.create_silence(payload.clone())?;
+    }
+    Ok(())
+}
+
+/// Compare the silences in a manifest against the existing entries on the targets it names.
+/// The manifest owns every entry on the subscriptions and clients it resolves to, and the
+/// entries on all subscriptions for the checks it silences everywhere - owned entries that
+/// are not in the manifest are cleared and all other entries are left alone.
+pub fn diff<B>(backend: &mut B, silences: Vec<SilenceOpts>) -> Result<ManifestDiff, SensuError>
+        where B: SilenceBackend {
+    let mut warnings = Vec::new();
+    let mut desired: Vec<SensuPayload> = Vec::new();
+    for s in silences {
+        for payload in silence_payloads(backend, s, &mut warnings)? {
+            let id = payload.entry_id();
+            if let Some(i) = desired.iter().position(|p| p.entry_id() == id) {
+                warnings.push(format!("Silence {} is defined more than once - using the last \
+                                       definition", id));
+                desired.remove(i);
+            }
+            desired.push(payload);
+        }
+    }
+
+    let now = unix_now();
+    let existing = backend.silences()?;
+    let owned = |s: &Silence| desired.iter().any(|p| match p.res {
+        Some(ref sub) => s.subscription.as_ref() == Some(sub),
+        None => s.subscription.is_none() && s.check == p.chk,
+    });
+    let clear: Vec<SensuPayload> = existing.iter()
+        .filter(|s| owned(s) && !desired.iter().any(|p| p.entry_id() == s.id))
+        .map(|s| SensuPayload { id: Some(s.id.clone()), ..SensuPayload::default() })
+        .collect();
+
+    let mut diff = ManifestDiff::default();
+    let (mut create, mut update) = (Vec::new(), Vec::new());
+    for payload in desired {
+        match existing.iter().find(|s| s.id == payload.entry_id()) {
+            Some(s) if up_to_date(s, &payload, now) => diff.unchanged.push(s.id.clone()),
+            Some(_) => update.push(payload),
+            None => create.push(payload),
+        }
+    }
+    diff.create = plan(backend, create, Vec::new(), false, B::plan_create)?.silences;
+    diff.update = plan(backend, update, Vec::new(), false, B::plan_create)?.silences;
+    diff.clear = plan(backend, clear, Vec::new(), false, B::plan_delete)?.silences;
+    diff.warnings = warnings;
+    Ok(diff)
+}
+
+/// Send the request for each of `from` in turn, moving it to `to` once it succeeded - the
+/// one that failed is left at the front of `from`
+fn send_all<B, F>(backend: &mut B, from: &mut Vec<PlannedSilence>, to: &mut Vec<PlannedSilence>,
+                  send: F) -> Result<(), SensuError>
+                  where B: SilenceBackend, F: Fn(&mut B, SensuPayload) -> Result<(), SensuError> {
+    while !from.is_empty() {
+        send(backend, from[0].payload.clone())?;
+        to.push(from.remove(0));
+    }
+    Ok(())
+}
+
+/// Create, update and clear entries until the targets of the manifest match it - see `diff`.
+/// Updates repost an entry under its ID, which replaces it in place, so no entry is ever
+/// cleared before its replacement exists. If a request fails, the error carries the changes
+/// made, the one that failed and the ones still pending.
+pub fn apply<B>(backend: &mut B, silences: Vec<SilenceOpts>)
+                -> Result<ManifestDiff, Interrupted<Box<PartialApply>>> where B: SilenceBackend {
+    let mut pending = match diff(backend, silences) {
+        Ok(diff) => diff,
+        Err(error) => return Err(Interrupted { report: Box::default(), error }),
+    };
+    let mut applied = ManifestDiff {
+        unchanged: pending.unchanged.split_off(0),
+        warnings: pending.warnings.split_off(0),
+        ..ManifestDiff::default()
+    };
+    let sent = send_all(backend, &mut pending.clear, &mut applied.clear, B::delete_silence)
+        .and_then(|_| send_all(backend, &mut pending.update, &mut applied.update,
+                               B::create_silence))
+        .and_then(|_| send_all(backend, &mut pending.create, &mut applied.create,
+                               B::create_silence));
+    match sent {
+        Ok(()) => Ok(applied),
+        Err(error) => {
+            let failed = [&mut pending.clear, &mut pending.update, &mut pending.create].iter_mut()
+                .find(|p| !p.is_empty()).map(|p| p.remove(0));
+            Err(Interrupted { report: Box::new(PartialApply { applied, failed, pending }), error })
+        },
+    }
+}
+
+/// Snapshot every silence entry in the backend
+pub fn export<B>(backend: &mut B) -> Result<Export, SensuError> where B: SilenceBackend {
+    Ok(Export::new(backend.silences()?, unix_now()))
+}
+
+fn import_payloads(export: Export, warnings: &mut Vec<String>) -> Vec<SensuPayload> {
+    let now = unix_now();
+    export.silences.into_iter().filter_map(|s| {
+        let id = s.id.clone();
+        let payload = s.into_payload(now);
+        if payload.is_none() {
+            warnings.push(format!("Silence {} has expired since the export - skipping...", id));
+        }
+        payload
+    }).collect()
+}
+
+/// Recreate the entries in a snapshot with their original deadlines, creators and reasons,
+/// skipping entries that have expired since
+pub fn import<B>(backend: &mut B, export: Export) -> Result<Report, SensuError>
+        where B: SilenceBackend {
+    let mut warnings = Vec::new();
+    let payloads = import_payloads(export, &mut warnings);
+    let mut report = Report { payloads: Vec::new(), warnings };
+    for payload in payloads {
+        backend.create_silence(payload.clone())?;
+        report.payloads.push(payload);
+    }
+    Ok(report)
+}
+
+/// Report the requests `import` would send
+pub fn plan_import<B>(backend: &mut B, export: Export) -> Result<Plan, SensuError>
+        where B: SilenceBackend {
+    let mut warnings = Vec::new();
+    let payloads = import_payloads(export, &mut warnings);
+    plan(backend, payloads, warnings, false, B::plan_create)
+}
+
+/// List silences matching the filters in `s` - if clients are given, only silences on the
+/// clients themselves, any of their subscriptions or all subscriptions are listed
+pub fn list<B>(backend: &mut B, s: ListOpts) -> Result<Validated<Silence>, SensuError>
+        where B: SilenceBackend {
+    let compile_regex = |string: Option<&str>| {
+        RegexBuilder::new(string.unwrap_or(".*")).size_limit(8192)
+            .dfa_size_limit(8192).build().map_err(SensuError::new_string)
+    };
+
+    let sub_regex: Regex = compile_regex(s.sub.as_deref())?;
+    let chk_regex: Regex = compile_regex(s.chk.as_deref())?;
+
+    let (clients, warnings) = match s.clients {
+        Some(res) => {
+            let validated = backend.resolve_clients(res)?;
+            if validated.items.is_empty() {
+                return Err(SensuError::new_string(format_args!(
+                    "{}\nNone of the requested clients exist", validated.warnings.join("\n")
+                )));
+            }
+            (Some(validated.items), validated.warnings)
+        },
+        None => (None, Vec::new()),
+    };
+    let applies = |o: &Silence| match (&clients, &o.subscription) {
+        (Some(cs), Some(sub)) => cs.iter().any(|c| c.subscriptions.contains(sub)),
+        _ => true,
+    };
+
+    let ListFilter { creator, never_expires, expires_within, target } = s.filter;
+    let creator_regex = match creator {
+        Some(c) => Some(compile_regex(Some(&c))?),
+        None => None,
+    };
+    let now = unix_now();
+    let prefix = backend.client_prefix();
+    let on_client = |o: &Silence| o.subscription.as_ref().map(|sub| sub.starts_with(prefix))
+        .unwrap_or(false);
+
+    let mut items: Vec<Silence> = backend.silences()?.into_iter().filter(|o| {
+        applies(o)
+            && sub_regex.is_match(o.subscription.as_deref().unwrap_or("all"))
+            && chk_regex.is_match(o.check.as_deref().unwrap_or("all"))
+            && creator_regex.as_ref()
+                .map(|r| r.is_match(o.creator.as_deref().unwrap_or("")))
+                .unwrap_or(true)
+            && (!never_expires || o.expires_at(now).is_none())
+            && expires_within.map(|w| {
+                o.expires_at(now).map(|at| at <= now.saturating_add(w as i64)).unwrap_or(false)
+            }).unwrap_or(true)
+            && match target {
+                Some(TargetType::Client) => on_client(o),
+                Some(TargetType::Subscription) => !on_client(o),
+                None => true,
+            }
+    }).collect();
+
+    // Entries without expiration or creator sort last
+    match s.sort {
+        Some(ListSort::Expiry) => items.sort_by_key(|o| (o.expires_at(now).is_none(),
+                                                         o.expires_at(now))),
+        Some(ListSort::Creator) => items.sort_by(|a, b| {
+            (a.creator.is_none(), &a.creator).cmp(&(b.creator.is_none(), &b.creator))
+        }),
+        None => (),
+    }
+    Ok(Validated { items, warnings })
+}
+
+/// Find the silence entries that suppress each of the checks in `q` on each of the clients in
+/// `q` right now
+pub fn query<B>(backend: &mut B, q: QueryOpts) -> Result<Validated<Coverage>, SensuError>
+        where B: SilenceBackend {
+    let clients = backend.resolve_clients(q.client)?;
+    if clients.items.is_empty() {
+        return Err(SensuError::new_string(format_args!(
+            "{}\nNone of the requested clients exist", clients.warnings.join("\n")
+        )));
+    }
+    let now = unix_now();
+    let silences = backend.silences()?;
+    let items = iproduct!(clients.items.iter(), q.checks.iter()).map(|(c, check)| {
+        let matching: Vec<Silence> = silences.iter().filter(|s| s.silences(c, check, now))
+            .cloned().collect();
+        Coverage {
+            client: c.name.clone(),
+            check: check.clone(),
+            silenced: !matching.is_empty(),
+            silences: matching,
+        }
+    }).collect();
+    Ok(Validated { items, warnings: clients.warnings })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::{SystemTime,UNIX_EPOCH};
+    use serde_json;
+
+    use backend::{missing_target,MemoryBackend};
+    use output::OutputFormat;
+    use resources::{ShushResources,ShushResourceType};
+    use sensu::{ClientInfo,SensuApi};
+
+    fn backend() -> MemoryBackend {
+        let mut backend = MemoryBackend::default();
+        backend.clients.push(serde_json::from_str(
+            r#"{"name": "host1", "subscriptions": ["linux"], "instance_id": "i-abc"}"#
+        ).unwrap());
+        backend.checks.insert("check_disk".to_string());
+        backend
+    }
+
+    fn now() -> i64 {
+        SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() as i64
+    }
+
+    fn ids(backend: &MemoryBackend) -> Vec<&str> {
+        backend.silenced.iter().map(|s| s.id.as_str()).collect()
+    }
+
+    #[test]
+    fn test_silence_filters_unknown_targets() {
+        let mut backend = backend();
+        let report = silence(&mut backend, SilenceOpts {
+            resources: Some(ShushResources {
+                res_type: ShushResourceType::Node,
+                resources: vec!["i-abc".to_string(), "i-missing".to_string()],
+            }),
+            checks: Some(vec!["check_disk".to_string(), "check_missing".to_string()]),
+            expire: Expire::Expire(3600, false),
+            reason: None,
+            begin: None,
+        }).unwrap();
+        assert_eq!(ids(&backend), vec!["client:host1:check_disk"]);
+        assert_eq!(report.payloads.len(), 1);
+        assert_eq!(report.warnings, vec![
+            "Check check_missing does not exist - filtering...".to_string(),
+            missing_target(&ShushResourceType::Node, "i-missing"),
+        ]);
+    }
+
+    #[test]
+    fn test_silence_by_attribute() {
+        let mut backend = backend();
+        backend.clients.push(serde_json::from_str(
+            r#"{"name": "host2", "ec2": {"region": "us-east-1", "instance_id": "i-def"},
+                "roles": ["web", "db"]}"#
+        ).unwrap());
+        backend.clients.push(serde_json::from_str(
+            r#"{"name": "host3", "ec2": {"region": "us-east-1"}}"#
+        ).unwrap());
+        silence(&mut backend, SilenceOpts {
+            resources: Some(ShushResources {
+                res_type: ShushResourceType::Attribute("/ec2/region".to_string()),
+                resources: vec!["us-east-1".to_string()],
+            }),
+            checks: None,
+            expire: Expire::Expire(3600, false),
+            reason: None,
+            begin: None,
+        }).unwrap();
+        assert_eq!(ids(&backend), vec!["client:host2:*", "client:host3:*"]);
+
+        backend.silenced.clear();
+        for (pointer, value) in &[("/name", "host1"), ("/subscriptions", "linux"),
+                                  ("/roles", "db")] {
+            silence(&mut backend, SilenceOpts {
+                resources: Some(ShushResources {
+                    res_type: ShushResourceType::Attribute(pointer.to_string()),
+                    resources: vec![value.to_string()],
+                }),
+                checks: None,
+                expire: Expire::Expire(3600, false),
+                reason: None,
+                begin: None,
+            }).unwrap();
+        }
+        assert_eq!(ids(&backend), vec!["client:host1:*", "client:host2:*"]);
+
+        let report = silence(&mut backend, SilenceOpts {
+            resources: Some(ShushResources {
+                res_type: ShushResourceType::Attribute("/roles".to_string()),
+                resources: vec!["web".to_string(), "db".to_string()],
+            }),
+            checks: None,
+            expire: Expire::Expire(3600, false),
+            reason: None,
+            begin: None,
+        }).unwrap();
+        assert_eq!(report.payloads.len(), 1);
+    }
+
+    #[test]
+    fn test_silence_resolved() {
+        let mut backend = backend();
+        let gone: ClientInfo = serde_json::from_str(r#"{"name": "gone"}"#).unwrap();
+        silence(&mut backend, SilenceOpts {
+            resources: Some(ShushResources {
+                res_type: ShushResourceType::Resolved(vec![gone]),
+                resources: vec!["gone".to_string()],
+            }),
+            checks: None,
+            expire: Expire::Expire(3600, false),
+            reason: None,
+            begin: None,
+        }).unwrap();
+        assert_eq!(ids(&backend), vec!["client:gone:*"]);
+    }
+
+    #[test]
+    fn test_plan_silence_sends_nothing() {
+        let mut backend = backend();
+        let plan = plan_silence(&mut backend, SilenceOpts {
+            resources: Some(ShushResources {
+                res_type: ShushResourceType::Sub,
+                resources: vec!["linux".to_string()],
+            }),
+            checks: Some(vec!["check_disk".to_string()]),
+            expire: Expire::Expire(3600, false),
+            reason: Some("disk replacement".to_string()),
+            begin: Some(now() + 600),
+        }).unwrap();
+        assert!(backend.silenced.is_empty());
+        assert_eq!(plan.silences.len(), 1);
+        assert_eq!(plan.silences[0].clients, Some(1));
+        assert_eq!(plan.silences[0].request.uri, "/silenced");
+        let body = plan.silences[0].request.body.as_ref().unwrap();
+        assert_eq!(body.get("subscription"), Some(&serde_json::Value::from("linux")));
+        // Sensu 1.x counts the expiration from creation rather than the begin time
+        let expire = body.get("expire").and_then(|e| e.as_i64()).unwrap();
+        assert!(expire > 4190 && expire <= 4200, "unexpected expire {}", expire);
+        assert_eq!(body.get("reason"), Some(&serde_json::Value::from("disk replacement")));
+    }
+
+    #[test]
+    fn test_clear_and_list() {
+        let mut backend = backend();
+        silence(&mut backend, SilenceOpts {
+            resources: Some(ShushResources {
+                res_type: ShushResourceType::Sub,
+                resources: vec!["linux".to_string()],
+            }),
+            checks: None,
+            expire: Expire::NoExpiration(false),
+            reason: Some("migration".to_string()),
+            begin: None,
+        }).unwrap();
+        assert_eq!(backend.silenced[0].reason, Some("migration".to_string()));
+        assert_eq!(ids(&backend), vec!["linux:*"]);
+        assert_eq!(list(&mut backend, ListOpts {
+            clients: None,
+            sub: Some("^lin".to_string()),
+            chk: None,
+            filter: ListFilter::default(),
+            sort: None,
+            output: OutputFormat::Text,
+        }).unwrap().items.len(), 1);
+        assert!(list(&mut backend, ListOpts {
+            clients: None,
+            sub: Some("^client:".to_string()),
+            chk: None,
+            filter: ListFilter::default(),
+            sort: None,
+            output: OutputFormat::Text,
+        }).unwrap().items.is_empty());
+        clear(&mut backend, ClearOpts {
+            resources: Some(ShushResources {
+                res_type: ShushResourceType::Sub,
+                resources: vec!["linux".to_string()],
+            }),
+            checks: None,
+            ids: None,
+        }).unwrap();
+        assert!(backend.silenced.is_empty());
+    }
+
+    #[test]
+    fn test_list_for_client() {
+        let mut backend = backend();
+        backend.clients.push(serde_json::from_str(
+            r#"{"name": "host2", "subscriptions": ["windows"]}"#
+        ).unwrap());
+        for &(res, chk) in &[(Some("client:host1"), None), (Some("client:host2"), None),
+                             (Some("linux"), Some("check_disk")), (Some("windows"), None),
+                             (None, Some("check_load"))] {
+            backend.create_silence(SensuPayload {
+                res: res.map(|r| r.to_string()),
+                chk: chk.map(|c| c.to_string()),
+                ..SensuPayload::default()
+            }).unwrap();
+        }
+        let listed = list(&mut backend, ListOpts {
+            clients: Some(ShushResources {
+                res_type: ShushResourceType::Node,
+                resources: vec!["i-abc".to_string(), "i-missing".to_string()],
+            }),
+            sub: None,
+            chk: None,
+            filter: ListFilter::default(),
+            sort: None,
+            output: OutputFormat::Text,
+        }).unwrap();
+        let ids: Vec<&str> = listed.items.iter().map(|s| s.id.as_str()).collect();
+        assert_eq!(ids, vec!["client:host1:*", "linux:check_disk", "*:check_load"]);
+        assert_eq!(listed.warnings, vec![missing_target(&ShushResourceType::Node, "i-missing")]);
+    }
+
+    #[test]
+    fn test_list_filters() {
+        let mut backend = backend();
+        backend.silenced = serde_json::from_str(r#"[
+            {"id": "client:host1:*", "subscription": "client:host1", "expire": 600,
+             "creator": "ops"},
+            {"id": "linux:*", "subscription": "linux", "expire": -1, "creator": "alice"},
+            {"id": "*:check_disk", "check": "check_disk", "expire": 7200},
+            {"id": "web:*", "subscription": "web", "expire": 60, "creator": "alice"}
+        ]"#).unwrap();
+        let mut listed = |filter: ListFilter, sort: Option<ListSort>| -> Vec<String> {
+            list(&mut backend, ListOpts {
+                clients: None,
+                sub: None,
+                chk: None,
+                filter,
+                sort,
+                output: OutputFormat::Text,
+            }).unwrap().items.into_iter().map(|s| s.id).collect()
+        };
+        assert_eq!(listed(ListFilter {
+            creator: Some("^alice$".to_string()),
+            ..ListFilter::default()
+        }, None), vec!["linux:*", "web:*"]);
+        assert_eq!(listed(ListFilter { never_expires: true, ..ListFilter::default() }, None),
+                   vec!["linux:*"]);
+        assert_eq!(listed(ListFilter { expires_within: Some(3600), ..ListFilter::default() },
+                          Some(ListSort::Expiry)), vec!["web:*", "client:host1:*"]);
+        assert_eq!(listed(ListFilter {
+            target: Some(TargetType::Client),
+            ..ListFilter::default()
+        }, None), vec!["client:host1:*"]);
+        assert_eq!(listed(ListFilter {
+            target: Some(TargetType::Subscription),
+            ..ListFilter::default()
+        }, None), vec!["linux:*", "*:check_disk", "web:*"]);
+        assert_eq!(listed(ListFilter::default(), Some(ListSort::Expiry)),
+                   vec!["web:*", "client:host1:*", "*:check_disk", "linux:*"]);
+        assert_eq!(listed(ListFilter::default(), Some(ListSort::Creator)),
+                   vec!["linux:*", "web:*", "client:host1:*", "*:check_disk"]);
+    }
+
+    #[test]
+    fn test_diff_and_apply() {
+        let mut backend = backend();
+        let existing: Vec<Silence> = serde_json::from_str(r#"[
+            {"id": "linux:check_load", "subscription": "linux", "check": "check_load",
+             "expire": -1},
+            {"id": "client:host1:*", "subscription": "client:host1", "expire": -1,
+             "reason": "db"},
+            {"id": "*:check_disk", "check": "check_disk", "expire": -1, "reason": "old"},
+            {"id": "web:*", "subscription": "web", "expire": -1}
+        ]"#).unwrap();
+        backend.silenced = existing.clone();
+        let manifest = || vec![
+            SilenceOpts {
+                resources: Some(ShushResources {
+                    res_type: ShushResourceType::Sub,
+                    resources: vec!["linux".to_string()],
+                }),
+                checks: Some(vec!["check_disk".to_string()]),
+                expire: Expire::Expire(3600, false),
+                reason: Some("x".to_string()),
+                begin: None,
+            },
+            SilenceOpts {
+                resources: Some(ShushResources {
+                    res_type: ShushResourceType::Client,
+                    resources: vec!["host1".to_string()],
+                }),
+                checks: None,
+                expire: Expire::NoExpiration(false),
+                reason: Some("db".to_string()),
+                begin: None,
+            },
+            SilenceOpts {
+                resources: None,
+                checks: Some(vec!["check_disk".to_string()]),
+                expire: Expire::NoExpiration(false),
+                reason: Some("new".to_string()),
+                begin: None,
+            },
+        ];
+        let ids = |planned: &[PlannedSilence]| -> Vec<String> {
+            planned.iter().map(|p| p.payload.entry_id()).collect()
+        };
+
+        let planned = diff(&mut backend, manifest()).unwrap();
+        assert_eq!(ids(&planned.create), vec!["linux:check_disk"]);
+        assert_eq!(ids(&planned.update), vec!["*:check_disk"]);
+        assert_eq!(ids(&planned.clear), vec!["linux:check_load"]);
+        assert_eq!(planned.unchanged, vec!["client:host1:*"]);
+        assert_eq!(backend.silenced.len(), 4);
+
+        apply(&mut backend, manifest()).unwrap();
+        let mut applied: Vec<&str> = backend.silenced.iter().map(|s| s.id.as_str()).collect();
+        applied.sort();
+        assert_eq!(applied, vec!["*:check_disk", "client:host1:*", "linux:check_disk", "web:*"]);
+
+        let planned = diff(&mut backend, manifest()).unwrap();
+        assert!(planned.create.is_empty() && planned.update.is_empty() && planned.clear.is_empty());
+        assert_eq!(planned.unchanged.len(), 3);
+
+        backend.silenced = existing;
+        backend.failing.insert("*:check_disk".to_string());
+        let partial = apply(&mut backend, manifest()).unwrap_err().report;
+        assert_eq!(ids(&partial.applied.clear), vec!["linux:check_load"]);
+        assert_eq!(partial.failed.map(|p| p.payload.entry_id()), Some("*:check_disk".to_string()));
+        assert_eq!(ids(&partial.pending.create), vec!["linux:check_disk"]);
+        let old = backend.silenced.iter().find(|s| s.id == "*:check_disk").unwrap();
+        assert_eq!(old.reason, Some("old".to_string()));
+    }
+
+    #[test]
+    fn test_export_and_import() {
+        let mut backend = backend();
+        let now = now();
+        backend.silenced = serde_json::from_str(&format!(r#"[
+            {{"id": "linux:*", "subscription": "linux", "expire": 600, "creator": "ops",
+              "reason": "patching"}},
+            {{"id": "*:check_disk", "check": "check_disk", "expire": -1}},
+            {{"id": "web:*", "subscription": "web", "expire_at": {}}}
+        ]"#, now - 10)).unwrap();
+        let export = export(&mut backend).unwrap();
+        backend.silenced.clear();
+
+        let report = import(&mut backend, export).unwrap();
+        assert_eq!(report.warnings,
+                   vec!["Silence web:* has expired since the export - skipping...".to_string()]);
+        assert_eq!(ids(&backend), vec!["linux:*", "*:check_disk"]);
+        assert_eq!(backend.silenced[0].creator, Some("ops".to_string()));
+        assert_eq!(backend.silenced[0].reason, Some("patching".to_string()));
+        assert!(backend.silenced[0].expire.map(|e| e > 590 && e <= 600).unwrap_or(false));
+        assert_eq!(backend.silenced[1].expire, None);
+    }
+
+    #[test]
+    fn test_extend() {
+        let mut backend = backend();
+        backend.silenced = serde_json::from_str(r#"[
+            {"id": "linux:*", "subscription": "linux", "expire": 600, "creator": "ops",
+             "reason": "patching", "expire_on_resolve": true},
+            {"id": "*:check_disk", "check": "check_disk", "expire": -1, "creator": "ops"},
+            {"id": "web:*", "subscription": "web", "expire": 60, "creator": "dev"}
+        ]"#).unwrap();
+
+        let report = extend(&mut backend, ExtendOpts {
+            selection: ListOpts {
+                filter: ListFilter {
+                    creator: Some("^ops$".to_string()),
+                    ..ListFilter::default()
+                },
+                ..ListOpts::default()
+            },
+            ids: None,
+            extension: Extension::Add(3600),
+        }).unwrap();
+        assert_eq!(report.warnings,
+                   vec!["Silence *:check_disk never expires - skipping...".to_string()]);
+        let linux = backend.silenced.iter().find(|s| s.id == "linux:*").unwrap();
+        assert!(linux.expire.map(|e| e > 4190 && e <= 4200).unwrap_or(false));
+        assert_eq!(linux.creator, Some("ops".to_string()));
+        assert_eq!(linux.reason, Some("patching".to_string()));
+        assert!(linux.expire_on_resolve);
+
+        extend(&mut backend, ExtendOpts {
+            selection: ListOpts::default(),
+            ids: Some(vec!["web:*".to_string(), "db:*".to_string()]),
+            extension: Extension::For(7200),
+        }).unwrap();
+        let web = backend.silenced.iter().find(|s| s.id == "web:*").unwrap();
+        assert_eq!(web.expire, Some(7200));
+        assert_eq!(web.creator, Some("dev".to_string()));
+
+        backend.silenced.push(serde_json::from_str(
+            r#"{"id": "webhooks:*", "subscription": "webhooks", "expire": 60}"#
+        ).unwrap());
+        let report = extend(&mut backend, ExtendOpts {
+            selection: ListOpts { sub: Some("web".to_string()), ..ListOpts::default() },
+            ids: None,
+            extension: Extension::For(600),
+        }).unwrap();
+        assert_eq!(report.payloads.iter().map(|p| p.entry_id()).collect::<Vec<_>>(),
+                   vec!["web:*"]);
+    }
+
+    #[test]
+    fn test_query() {
+        let mut backend = backend();
+        backend.create_silence(SensuPayload {
+            res: Some("linux".to_string()),
+            expire: Some(Expire::NoExpiration(true)),
+            ..SensuPayload::default()
+        }).unwrap();
+        let query = |backend: &mut MemoryBackend, check: &str| query(backend, QueryOpts {
+            client: ShushResources {
+                res_type: ShushResourceType::Client,
+                resources: vec!["host1".to_string()],
+            },
+            checks: check.split(',').map(|c| c.to_string()).collect(),
+            output: OutputFormat::Text,
+        }).unwrap().items;
+        let coverage = query(&mut backend, "check_disk");
+        assert!(coverage[0].silenced);
+        assert!(coverage[0].silences[0].expire_on_resolve);
+        clear(&mut backend, ClearOpts {
+            resources: None,
+            checks: None,
+            ids: Some(vec!["linux:*".to_string()]),
+        }).unwrap();
+        assert!(!query(&mut backend, "check_disk")[0].silenced);
+        backend.create_silence(SensuPayload {
+            chk: Some("check_load".to_string()),
+            ..SensuPayload::default()
+        }).unwrap();
+        let coverage = query(&mut backend, "check_disk,check_load");
+        assert_eq!(coverage.iter().map(|c| (c.check.as_str(), c.silenced)).collect::<Vec<_>>(),
+                   vec![("check_disk", false), ("check_load", true)]);
+    }
+
+    #[test]
+    fn test_clear_by_id() {
+        let mut backend = backend();
+        for chk in &["check_disk", "check_load"] {
+            backend.create_silence(SensuPayload {
+                res: Some("client:host1".to_string()),
+                chk: Some(chk.to_string()),
+                ..SensuPayload::default()
+            }).unwrap();
+        }
+        let report = clear(&mut backend, ClearOpts {
+            resources: None,
+            checks: None,
+            ids: Some(vec!["client:host1:check_load".to_string(), "linux:*".to_string()]),
+        }).unwrap();
+        assert_eq!(ids(&backend), vec!["client:host1:check_disk"]);
+        assert_eq!(report.warnings, vec!["Silence ID linux:* does not exist - filtering...".to_string()]);
+        assert_eq!(report.payloads[0].clone().into_body(&SensuApi::Legacy).unwrap().get("id"),
+                   Some(&serde_json::Value::from("client:host1:check_load")));
+    }
+}
@@ -0,0 +1,164 @@
+use std::collections::HashSet;
+
+use hyper::{Method,Uri};
+use serde_json::{self,Value};
+
+use super::{missing_target,PlannedRequest,SilenceBackend,Validated};
+use err::SensuError;
+use resources::{ShushResources,ShushResourceType};
+use selector::Selector;
+use sensu::{silenced_name,ClientInfo,SensuApi,SensuPayload,Silence};
+
+/// In-memory backend for exercising shush logic without a monitoring server
+#[derive(Debug,Default)]
+pub struct MemoryBackend {
+    /// Known clients - instance IDs are read from the `instance_id` attribute and other
+    /// attributes are looked up by JSON pointer
+    pub clients: Vec<ClientInfo>,
+    /// Known checks
+    pub checks: HashSet<String>,
+    /// Silence entries
+    pub silenced: Vec<Silence>,
+    /// IDs of entries whose create and delete requests fail, for exercising partial failures
+    pub failing: HashSet<String>,
+}
+
+impl MemoryBackend {
+    fn check_failing(&self, payload: &SensuPayload) -> Result<(), SensuError> {
+        let id = payload.entry_id();
+        if self.failing.contains(&id) {
+            return Err(SensuError::new_string(format_args!("Request for {} failed", id)));
+        }
+        Ok(())
+    }
+
+    fn position(&self, payload: &SensuPayload) -> Option<usize> {
+        let id = payload.entry_id();
+        self.silenced.iter().position(|s| s.id == id)
+    }
+}
+
+impl SilenceBackend for MemoryBackend {
+    fn resolve_targets(&mut self, res: ShushResources) -> Result<Validated<String>, SensuError> {
+        if res.res_type != ShushResourceType::Sub {
+            let clients = self.resolve_clients(res)?;
+            let prefix = self.client_prefix();
+            return Ok(Validated {
+                items: clients.items.into_iter().map(|c| format!("{}{}", prefix, c.name)).collect(),
+                warnings: clients.warnings,
+            });
+        }
+        let mut resolved = Validated::default();
+        for sub in res.resources {
+            if self.clients.iter().any(|c| c.subscriptions.contains(&sub)) {
+                resolved.items.push(sub);
+            } else {
+                resolved.warnings.push(missing_target(&ShushResourceType::Sub, &sub));
+            }
+        }
+        Ok(resolved)
+    }
+
+    fn resolve_clients(&mut self, res: ShushResources)
+                       -> Result<Validated<ClientInfo>, SensuError> {
+        let api = SensuApi::Legacy;
+        let mut resolved = Validated::default();
+        let res_type = res.res_type;
+        for target in res.resources {
+            let selector = match res_type {
+                ShushResourceType::Selector => Some(Selector::parse(&target)?),
+                _ => None,
+            };
+            let known = match res_type {
+                ShushResourceType::Resolved(ref clients) => clients,
+                _ => &self.clients,
+            };
+            let found: Vec<ClientInfo> = known.iter().filter(|c| match res_type {
+                ShushResourceType::Client | ShushResourceType::Resolved(_) => c.name == target,
+                ShushResourceType::Sub => c.subscriptions.contains(&target),
+                ShushResourceType::Selector => selector.as_ref().map(|s| s.matches(c))
+                    .unwrap_or(false),
+                ref lookup => c.attribute_strings(api.lookup_pointer(lookup).unwrap_or_default())
+                    .contains(&target),
+            }).cloned().collect();
+            if found.is_empty() {
+                resolved.warnings.push(missing_target(&res_type, &target));
+            }
+            resolved.items.extend(found.into_iter().map(|mut c| {
+                let client_sub = format!("{}{}", api.client_prefix(), c.name);
+                if !c.subscriptions.contains(&client_sub) {
+                    c.subscriptions.push(client_sub);
+                }
+                c
+            }));
+        }
+        // A client matching several targets is only resolved once
+        let mut seen = HashSet::new();
+        resolved.items.retain(|c| seen.insert(c.name.clone()));
+        Ok(resolved)
+    }
+
+    fn validate_checks(&mut self, checks: Vec<String>) -> Validated<String> {
+        let mut validated = Validated::default();
+        for chk in checks {
+            if self.checks.contains(&chk) {
+                validated.items.push(chk);
+            } else {
+                validated.warnings.push(format!("Check {} does not exist - filtering...", chk));
+            }
+        }
+        validated
+    }
+
+    fn client_prefix(&self) -> &'static str {
+        SensuApi::Legacy.client_prefix()
+    }
+
+    fn clients(&mut self) -> Result<Vec<ClientInfo>, SensuError> {
+        Ok(self.clients.clone())
+    }
+
+    fn plan_create(&self, payload: &SensuPayload) -> Result<PlannedRequest, SensuError> {
+        Ok(PlannedRequest {
+            method: Method::POST,
+            uri: Uri::from_static("/silenced"),
+            body: Some(payload.clone().into_body(&SensuApi::Legacy)?),
+        })
+    }
+
+    fn plan_delete(&self, payload: &SensuPayload) -> Result<PlannedRequest, SensuError> {
+        Ok(PlannedRequest {
+            method: Method::POST,
+            uri: Uri::from_static("/silenced/clear"),
+            body: Some(payload.clone().into_body(&SensuApi::Legacy)?),
+        })
+    }
+
+    fn create_silence(&mut self, payload: SensuPayload) -> Result<(), SensuError> {
+        self.check_failing(&payload)?;
+        if let Some(i) = self.position(&payload) {
+            self.silenced.remove(i);
+        }
+        let id = silenced_name(payload.res.as_deref(), payload.chk.as_deref());
+        let mut body = payload.into_body(&SensuApi::Legacy)?;
+        body.insert("id".to_string(), Value::String(id));
+        let silence = serde_json::from_value(Value::Object(body)).map_err(SensuError::new_string)?;
+        self.silenced.push(silence);
+        Ok(())
+    }
+
+    fn delete_silence(&mut self, payload: SensuPayload) -> Result<(), SensuError> {
+        self.check_failing(&payload)?;
+        match self.position(&payload) {
+            Some(i) => {
+                self.silenced.remove(i);
+                Ok(())
+            },
+            None => Err(SensuError::not_found()),
+        }
+    }
+
+    fn silences(&mut self) -> Result<Vec<Silence>, SensuError> {
+        Ok(self.silenced.clone())
+    }
+}
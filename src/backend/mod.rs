@@ -0,0 +1,107 @@
+//! Abstraction over the monitoring system that silences are managed in
+
+use std::fmt::{self,Display};
+
+use hyper::{Method,Uri};
+use serde_json::{self,Map,Value};
+
+use err::SensuError;
+use resources::{ShushResources,ShushResourceType};
+use sensu::{ClientInfo,SensuPayload,Silence};
+
+mod memory;
+pub use self::memory::*;
+
+/// Items that passed validation along with warnings about any that were dropped
+#[derive(Clone,Debug,PartialEq)]
+pub struct Validated<T> {
+    /// Items that passed validation
+    pub items: Vec<T>,
+    /// Messages about dropped items or skipped validation
+    pub warnings: Vec<String>,
+}
+
+impl<T> Default for Validated<T> {
+    fn default() -> Self {
+        Validated { items: Vec::new(), warnings: Vec::new() }
+    }
+}
+
+/// Request a backend sends to create or delete a silence entry
+#[derive(Clone,Debug,PartialEq)]
+pub struct PlannedRequest {
+    /// HTTP method
+    pub method: Method,
+    /// Full URI of the endpoint
+    pub uri: Uri,
+    /// JSON body, if any
+    pub body: Option<Map<String, Value>>,
+}
+
+impl Display for PlannedRequest {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.method, self.uri)?;
+        if let Some(ref body) = self.body {
+            let body_string = serde_json::to_string_pretty(body).map_err(|_| fmt::Error)?;
+            write!(f, "\n{}", body_string)?;
+        }
+        Ok(())
+    }
+}
+
+/// Warning for a target that resolves to nothing, worded the same by every backend
+pub fn missing_target(res_type: &ShushResourceType, target: &str) -> String {
+    match *res_type {
+        ShushResourceType::Node => format!(
+            "Instance ID {} not associated with Sensu client ID - filtering...\n\
+             If you recently provisioned an instance, please wait for it to register with Sensu",
+            target
+        ),
+        ShushResourceType::Client | ShushResourceType::Resolved(_) => {
+            format!("Client {} does not exist - filtering...", target)
+        },
+        ShushResourceType::Sub => format!("Subscription {} does not exist - filtering...", target),
+        ShushResourceType::Attribute(ref pointer) => {
+            format!("No client has {} \"{}\" - filtering...", pointer, target)
+        },
+        ShushResourceType::Selector => {
+            format!("Selector \"{}\" matches no clients - filtering...", target)
+        },
+    }
+}
+
+/// Operations shush needs from a monitoring system to silence, clear and list
+pub trait SilenceBackend {
+    /// Resolve targets into the subscription strings silences are created for, dropping any
+    /// target that the backend does not know about
+    fn resolve_targets(&mut self, res: ShushResources) -> Result<Validated<String>, SensuError>;
+
+    /// Resolve targets into the clients they refer to, with the implicit per-client
+    /// subscription included in the subscriptions of each client
+    fn resolve_clients(&mut self, res: ShushResources)
+                       -> Result<Validated<ClientInfo>, SensuError>;
+
+    /// Filter out checks that the backend does not know about
+    fn validate_checks(&mut self, checks: Vec<String>) -> Validated<String>;
+
+    /// Prefix of the implicit per-client subscription, such as `client:`
+    fn client_prefix(&self) -> &'static str;
+
+    /// Enumerate all clients known to the backend
+    fn clients(&mut self) -> Result<Vec<ClientInfo>, SensuError>;
+
+    /// Request that `create_silence` sends for the payload
+    fn plan_create(&self, payload: &SensuPayload) -> Result<PlannedRequest, SensuError>;
+
+    /// Request that `delete_silence` sends for the payload
+    fn plan_delete(&self, payload: &SensuPayload) -> Result<PlannedRequest, SensuError>;
+
+    /// Create a silence entry
+    fn create_silence(&mut self, payload: SensuPayload) -> Result<(), SensuError>;
+
+    /// Delete the silence entry matching the subscription and check in the payload
+    fn delete_silence(&mut self, payload: SensuPayload) -> Result<(), SensuError>;
+
+    /// Enumerate all silence entries
+    fn silences(&mut self) -> Result<Vec<Silence>, SensuError>;
+}
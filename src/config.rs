@@ -1,23 +1,21 @@
+//! Shush config file parsing with environment variable substitution
+
 use std::collections::HashMap;
 #[cfg(not(test))]
 use std::env;
 use std::env::VarError;
+use std::fs;
 use std::path::Path;
-#[cfg(not(test))]
-use std::process;
+use std::process::Command;
 
+use chrono_tz::Tz;
 use ini::Ini;
 use nom::IResult;
 use nom::bytes::streaming::{tag,take_until};
 use nom::combinator::rest;
 use nom::error::ErrorKind;
 
-#[cfg(test)]
-mod process {
-    pub fn exit(exit_code: u32) -> ! {
-        panic!(format!("Panicked with exit code {}", exit_code))
-    }
-}
+use err::SensuError;
 
 #[cfg(test)]
 mod env {
@@ -40,14 +38,8 @@ fn get_env_var(i: &str) -> IResult<&str, String> {
     let (i, _) = tag("}")(i)?;
     let resolved_env_var = match env::var(var_name) {
         Ok(e) => e,
-        Err(VarError::NotPresent) => {
-            println!("Variable {} is not present", var_name);
-            return Err(nom::Err::Failure((i_backtrack, ErrorKind::ParseTo)));
-        },
-        Err(VarError::NotUnicode(_)) => {
-            println!("Variable {} is not unicode", var_name);
-            return Err(nom::Err::Failure((i_backtrack, ErrorKind::ParseTo)));
-        }
+        // Fail at the variable name so that the error can point to it
+        Err(_) => return Err(nom::Err::Failure((i_backtrack, ErrorKind::ParseTo))),
     };
     Ok((i, o.to_string() + &resolved_env_var))
 }
@@ -59,12 +51,12 @@ fn sub_or_rest(input: &str) -> IResult<&str, String> {
         let (sub_i, sub_o) = rest(input)?;
         (sub_i, sub_o.to_string())
     };
-    Ok((i, o.to_string()))
+    Ok((i, o))
 }
 
 fn substitute_vars(input: &str) -> IResult<&str, String> {
     let (mut i, mut o) = sub_or_rest(input)?;
-    while i.len() > 0 {
+    while !i.is_empty() {
         let (loop_i, loop_o) = sub_or_rest(i)?;
         i = loop_i;
         o += &loop_o;
@@ -72,61 +64,130 @@ fn substitute_vars(input: &str) -> IResult<&str, String> {
     Ok((i, o))
 }
 
+/// Substitute environment variables in the value of config option `key`
+fn substitute(key: &str, value: &str) -> Result<String, SensuError> {
+    match substitute_vars(value) {
+        Ok((_, out)) => Ok(out),
+        Err(nom::Err::Failure((var, ErrorKind::ParseTo))) => {
+            let name = var.split('}').next().unwrap_or(var);
+            Err(SensuError::new_string(format_args!(
+                "Config option {} uses variable {}, which is {}", key, name,
+                match env::var(name) {
+                    Err(VarError::NotUnicode(_)) => "not unicode",
+                    _ => "not present",
+                }
+            )))
+        },
+        Err(_) => Err(SensuError::new_string(format_args!(
+            "Failed to parse config option {}: {}", key, value
+        ))),
+    }
+}
+
+/// Keys recognized in the shush config file - each one is described under Configuration in
+/// the crate documentation
+const CONFIG_KEYS: &[&str] = &[
+    "api", "backend", "namespace",
+    "ca_bundle", "client_cert", "client_key", "insecure_skip_verify",
+    "user", "password", "password_file", "password_command",
+    "api_key", "api_key_file", "api_key_command",
+    "require_reason", "timezone", "client_config", "attributes",
+];
+
 /// Struct representing shush config file
 #[derive(Debug)]
 pub struct ShushConfig(HashMap<String, String>);
 
 impl ShushConfig {
-    /// Initialize ShushConfig with path
-    pub fn new(path: Option<String>) -> Self {
+    /// Load config from `path`, or from `/etc/shush/shush.conf` or `~/.shush/shush.conf` if
+    /// `path` is not given or not a file, substituting environment variables in every value
+    pub fn new(path: Option<String>) -> Result<Self, SensuError> {
         let parse_config = |path: String| {
+            let ini = Ini::load_from_file(path.as_str()).map_err(|e| SensuError::new_string(
+                format_args!("Failed to parse INI file {}: {}", path, e)
+            ))?;
             let mut hm = HashMap::new();
-            for (_, prop) in Ini::load_from_file(path.as_str()).unwrap_or_else(|e| {
-                println!("Failed to parse INI file: {}", e);
-                process::exit(1);
-            }) {
-                for (k, v) in &prop {
-                    if k == "api" {
-                        hm.insert(k.to_string(), v.to_string());
+            for (_, prop) in &ini {
+                for (k, v) in prop {
+                    if CONFIG_KEYS.contains(&k.as_str()) {
+                        hm.insert(k.to_string(), substitute(k, v)?);
                     }
                 }
             }
-            ShushConfig(hm)
+            Ok(ShushConfig(hm))
         };
-        let home_config = format!("{}/.shush/shush.conf", env::var("HOME").unwrap_or_else(|_| {
-            println!("$HOME environment variable not found - \
-                     defaulting to ~/.shush/shush.conf and this expansion may or may not work");
-            "~/.shush/shush.conf".to_string()
-        }));
-
-        if path.is_some() && Path::new(path.as_ref().unwrap().as_str()).is_file() {
-            parse_config(path.unwrap())
+        let home_config = env::var("HOME").ok().map(|h| format!("{}/.shush/shush.conf", h));
+
+        if let Some(p) = path.filter(|p| Path::new(p.as_str()).is_file()) {
+            parse_config(p)
         } else if Path::new("/etc/shush/shush.conf").is_file() {
             parse_config("/etc/shush/shush.conf".to_string())
-        } else if Path::new(home_config.as_str()).is_file() {
-            parse_config(home_config)
+        } else if let Some(h) = home_config.filter(|h| Path::new(h.as_str()).is_file()) {
+            parse_config(h)
         } else {
-            println!("No config found - exiting");
-            process::exit(1);
+            Err(SensuError::new("No config found"))
         }
     }
 
     /// Get config option from ShushConfig object
     pub fn get(&self, key: &str) -> Option<String> {
-        self.0.get(key).and_then(|val| {
-            if let Ok((_, out)) = substitute_vars(val.as_str()) {
-                Some(out)
-            } else {
-                println!("Failed to parse config file - exiting...");
-                process::exit(1);
+        self.0.get(key).cloned()
+    }
+
+    /// Get secret config option either directly, from the file named by `<key>_file` or from
+    /// the output of the shell command in `<key>_command`
+    pub fn get_secret(&self, key: &str) -> Result<Option<String>, SensuError> {
+        let trim = |s: String| s.trim_end_matches(&['\n', '\r'][..]).to_string();
+        if let Some(val) = self.get(key) {
+            Ok(Some(val))
+        } else if let Some(path) = self.get(&format!("{}_file", key)) {
+            fs::read_to_string(&path).map(|s| Some(trim(s))).map_err(|e| SensuError::new_string(
+                format_args!("Failed to read {} from {}: {}", key, path, e)
+            ))
+        } else if let Some(cmd) = self.get(&format!("{}_command", key)) {
+            let output = Command::new("sh").arg("-c").arg(&cmd).output().map_err(|e| {
+                SensuError::new_string(format_args!("Failed to run command for {}: {}", key, e))
+            })?;
+            if !output.status.success() {
+                return Err(SensuError::new_string(format_args!(
+                    "Command for {} exited with {}", key, output.status
+                )));
             }
-        })
+            Ok(Some(trim(String::from_utf8_lossy(&output.stdout).into_owned())))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Get boolean config option from ShushConfig object, defaulting to `false`
+    pub fn get_bool(&self, key: &str) -> Result<bool, SensuError> {
+        match self.get(key).as_ref().map(|v| v.to_lowercase()) {
+            Some(ref v) if v == "true" || v == "yes" || v == "1" => Ok(true),
+            Some(ref v) if v == "false" || v == "no" || v == "0" => Ok(false),
+            Some(v) => Err(SensuError::new_string(format_args!(
+                "Config option {} must be true or false, found {}", key, v
+            ))),
+            None => Ok(false),
+        }
+    }
+
+    /// Get time zone config option, failing if it is not an IANA time zone name
+    pub fn get_timezone(&self) -> Result<Option<Tz>, SensuError> {
+        match self.get("timezone") {
+            Some(tz) => tz.parse().map(Some).map_err(|_| SensuError::new_string(format_args!(
+                "Config option timezone must be an IANA time zone such as Europe/Berlin, \
+                 found {}", tz
+            ))),
+            None => Ok(None),
+        }
     }
 }
 
 #[cfg(test)]
 mod test {
-    use super::substitute_vars;
+    use std::collections::HashMap;
+
+    use super::{substitute,substitute_vars,ShushConfig};
 
     #[test]
     fn test_substitude_vars() {
@@ -151,4 +212,24 @@ mod test {
     fn test_substitude_vars_bad_syntax_failure() {
         substitute_vars("https://localhost/${ARG").unwrap();
     }
+
+    #[test]
+    fn test_substitute() {
+        assert_eq!(substitute("api", "https://${EXAMPLE_HOST}").unwrap(),
+                   "https://something.host.net");
+        assert_eq!(substitute("api", "https://${NOT_PRESENT}/x").unwrap_err().to_string(),
+                   "Config option api uses variable NOT_PRESENT, which is not present");
+        assert!(substitute("api", "https://${ARG").is_err());
+    }
+
+    #[test]
+    fn test_get_secret() {
+        let mut hm = HashMap::new();
+        hm.insert("password".to_string(), "plain".to_string());
+        hm.insert("api_key_command".to_string(), "printf 'from-cmd\\n'".to_string());
+        let config = ShushConfig(hm);
+        assert_eq!(config.get_secret("password").unwrap(), Some("plain".to_string()));
+        assert_eq!(config.get_secret("api_key").unwrap(), Some("from-cmd".to_string()));
+        assert_eq!(config.get_secret("token").unwrap(), None);
+    }
 }
@@ -9,19 +9,24 @@ use hyper;
 /// Error type for passing error messages to display from the CLI
 #[derive(Debug)]
 pub enum SensuError {
+    /// Requested resource does not exist
     NotFound,
+    /// Any other error with a message to display
     Message(String),
 }
 
 impl SensuError {
+    /// Create error from message
     pub fn new(msg: &str) -> Self {
         SensuError::Message(msg.to_string())
     }
 
+    /// Create error from anything that can be displayed
     pub fn new_string<F>(any_format: F) -> Self where F: Display {
         SensuError::Message(format!("{}", any_format))
     }
 
+    /// Create not found error
     pub fn not_found() -> Self {
         SensuError::NotFound
     }
@@ -29,22 +34,17 @@ impl SensuError {
 
 impl From<hyper::Error> for SensuError {
     fn from(e: hyper::Error) -> Self {
-        SensuError::new(e.description())
+        SensuError::new_string(e)
     }
 }
 
-impl Error for SensuError {
-    fn description(&self) -> &str {
-        if let SensuError::Message(string) = self {
-            string.as_ref()
-        } else {
-            "404 Not found"
-        }
-    }
-}
+impl Error for SensuError {}
 
 impl Display for SensuError {
     fn fmt(&self, f: &mut Formatter) -> Result<(), fmt::Error> {
-        write!(f, "{}", self.description())
+        match *self {
+            SensuError::Message(ref string) => write!(f, "{}", string),
+            SensuError::NotFound => write!(f, "404 Not found"),
+        }
     }
 }
@@ -0,0 +1,167 @@
+//! Running a command while its silences are held, for `shush exec`
+
+use std::mem;
+use std::os::unix::process::ExitStatusExt;
+use std::process::{Command,ExitStatus};
+use std::ptr;
+use std::sync::atomic::{AtomicUsize,Ordering};
+use std::sync::mpsc::{self,RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+
+use libc;
+
+use err::SensuError;
+
+/// How often the command is checked for having exited
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Last SIGINT or SIGTERM received while signals are caught, or 0 if none is pending
+static PENDING_SIGNAL: AtomicUsize = AtomicUsize::new(0);
+
+/// Last SIGINT or SIGTERM sent by another process, which the command does not receive on its
+/// own, or 0 if none is pending
+static SENT_SIGNAL: AtomicUsize = AtomicUsize::new(0);
+
+extern "C" fn record_signal(signal: libc::c_int, info: *mut libc::siginfo_t,
+                            _: *mut libc::c_void) {
+    PENDING_SIGNAL.store(signal as usize, Ordering::SeqCst);
+    // Signals from the terminal, such as Ctrl-C, go to the whole foreground process group and
+    // so reach the command already - only ones sent with kill(2) and the like have a code <= 0
+    if info.is_null() || unsafe { (*info).si_code } <= 0 {
+        SENT_SIGNAL.store(signal as usize, Ordering::SeqCst);
+    }
+}
+
+/// Guard that keeps SIGINT and SIGTERM from stopping shush while silences are held, so that
+/// they are always cleared again. Signals received are recorded instead and the previous
+/// handlers come back when the guard is dropped.
+pub struct Signals {
+    previous: Vec<(libc::c_int, libc::sigaction)>,
+}
+
+impl Signals {
+    /// Start catching SIGINT and SIGTERM
+    pub fn catch() -> Self {
+        PENDING_SIGNAL.store(0, Ordering::SeqCst);
+        SENT_SIGNAL.store(0, Ordering::SeqCst);
+        let previous = [libc::SIGINT, libc::SIGTERM].iter().map(|signal| unsafe {
+            let mut action: libc::sigaction = mem::zeroed();
+            action.sa_sigaction = record_signal as extern "C" fn(libc::c_int,
+                *mut libc::siginfo_t, *mut libc::c_void) as libc::sighandler_t;
+            action.sa_flags = libc::SA_SIGINFO | libc::SA_RESTART;
+            libc::sigemptyset(&mut action.sa_mask);
+            let mut previous: libc::sigaction = mem::zeroed();
+            libc::sigaction(*signal, &action, &mut previous);
+            (*signal, previous)
+        }).collect();
+        Signals { previous }
+    }
+
+    /// Signal received since the last call, if any - it is not forwarded to a command started
+    /// afterwards
+    pub fn take(&self) -> Option<i32> {
+        SENT_SIGNAL.store(0, Ordering::SeqCst);
+        match PENDING_SIGNAL.swap(0, Ordering::SeqCst) {
+            0 => None,
+            signal => Some(signal as i32),
+        }
+    }
+
+    /// Run `command` until it exits, calling `renew` every `interval` while it runs. Signals
+    /// sent to shush by another process while it runs are forwarded to it, while the ones
+    /// from the terminal reach it directly.
+    pub fn supervise<F>(&self, command: &[String], interval: Duration, mut renew: F)
+                        -> Result<ExitStatus, SensuError> where F: FnMut() {
+        let (program, args) = command.split_first()
+            .ok_or_else(|| SensuError::new("No command to run"))?;
+        let mut child = Command::new(program).args(args).spawn().map_err(|e| {
+            SensuError::new_string(format_args!("Failed to run {}: {}", program, e))
+        })?;
+
+        // Reap the command and forward signals on a thread of their own so that a slow
+        // renewal does not hold them up
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let status = loop {
+                match child.try_wait() {
+                    Ok(Some(status)) => break Ok(status),
+                    Ok(None) => (),
+                    Err(e) => break Err(e),
+                }
+                let signal = SENT_SIGNAL.swap(0, Ordering::SeqCst);
+                if signal != 0 {
+                    unsafe { libc::kill(child.id() as libc::pid_t, signal as libc::c_int) };
+                }
+                thread::sleep(POLL_INTERVAL);
+            };
+            let _ = sender.send(status);
+        });
+
+        loop {
+            match receiver.recv_timeout(interval) {
+                Ok(Ok(status)) => return Ok(status),
+                Ok(Err(e)) => return Err(SensuError::new_string(format_args!(
+                    "Failed to wait for {}: {}", program, e
+                ))),
+                Err(RecvTimeoutError::Timeout) => renew(),
+                Err(RecvTimeoutError::Disconnected) => return Err(SensuError::new_string(
+                    format_args!("Lost track of {}", program)
+                )),
+            }
+        }
+    }
+}
+
+impl Drop for Signals {
+    fn drop(&mut self) {
+        for (signal, action) in &self.previous {
+            unsafe { libc::sigaction(*signal, action, ptr::null_mut()) };
+        }
+    }
+}
+
+/// Exit code that passes the exit status of a command through - `128 + N` if it was killed by
+/// signal N, as shells report it
+pub fn exit_code(status: ExitStatus) -> i32 {
+    status.code().or_else(|| status.signal().map(|s| 128 + s)).unwrap_or(1)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn sh(script: &str) -> Vec<String> {
+        vec!["sh".to_string(), "-c".to_string(), script.to_string()]
+    }
+
+    #[test]
+    fn test_supervise() {
+        let signals = Signals::catch();
+        unsafe { libc::raise(libc::SIGTERM) };
+        assert_eq!(signals.take(), Some(libc::SIGTERM));
+        assert_eq!(signals.take(), None);
+
+        let mut renewals = 0;
+        let status = signals.supervise(&sh("sleep 1; exit 3"), Duration::from_millis(300),
+                                       || renewals += 1).unwrap();
+        assert_eq!(exit_code(status), 3);
+        assert!(renewals >= 2);
+
+        let status = signals.supervise(&sh("kill -9 $$"), Duration::from_secs(60), || ())
+            .unwrap();
+        assert_eq!(exit_code(status), 137);
+
+        assert!(signals.supervise(&["/nonexistent/command".to_string()],
+                                  Duration::from_secs(60), || ()).is_err());
+        assert!(signals.supervise(&[], Duration::from_secs(60), || ()).is_err());
+
+        thread::spawn(|| {
+            thread::sleep(Duration::from_millis(500));
+            unsafe { libc::kill(libc::getpid(), libc::SIGTERM) };
+        });
+        let status = signals.supervise(&sh("trap 'exit 5' TERM; sleep 3 & wait"),
+                                       Duration::from_secs(60), || ()).unwrap();
+        assert_eq!(exit_code(status), 5);
+    }
+}
@@ -0,0 +1,197 @@
+//! Versioned snapshots of silence entries for backing them up and restoring them
+
+use std::fs;
+use std::path::Path;
+
+use serde_json;
+use serde_yaml;
+
+use err::SensuError;
+use sensu::{current_user,Expire,SensuPayload,Silence};
+
+/// Version of the export format written by this version of shush
+pub const EXPORT_VERSION: u32 = 1;
+
+/// Snapshot of all silence entries, written as JSON if the file name ends in `.json` and as
+/// YAML otherwise
+#[derive(Clone,Debug,Deserialize,PartialEq,Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Export {
+    /// Version of the export format
+    pub version: u32,
+    /// UNIX timestamp at which the snapshot was taken
+    pub exported_at: i64,
+    /// Exported entries
+    pub silences: Vec<ExportedSilence>,
+}
+
+/// Silence entry with its expiration stored as an absolute deadline
+#[derive(Clone,Debug,Deserialize,PartialEq,Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ExportedSilence {
+    /// ID of the entry
+    pub id: String,
+    /// Subscription or client target - all targets if `None`
+    #[serde(default)]
+    pub subscription: Option<String>,
+    /// Check name - all checks if `None`
+    #[serde(default)]
+    pub check: Option<String>,
+    /// User who created the entry
+    #[serde(default)]
+    pub creator: Option<String>,
+    /// Reason the entry was created
+    #[serde(default)]
+    pub reason: Option<String>,
+    /// Whether the entry is cleared when the check resolves
+    #[serde(default)]
+    pub expire_on_resolve: bool,
+    /// UNIX timestamp at which the entry takes effect
+    #[serde(default)]
+    pub begin: Option<i64>,
+    /// UNIX timestamp at which the entry expires - never if `None`
+    #[serde(default)]
+    pub expire_at: Option<i64>,
+}
+
+impl ExportedSilence {
+    /// Record of the entry a payload creates when sent at the UNIX timestamp `now`
+    pub fn from_payload(payload: &SensuPayload, now: i64) -> Self {
+        let start = payload.begin.unwrap_or(now);
+        let (expire_at, eor) = match payload.expire {
+            Some(Expire::Expire(secs, eor)) => (Some(start.saturating_add(secs as i64)), eor),
+            Some(Expire::Until(ts, eor)) => (Some(ts), eor),
+            Some(Expire::NoExpiration(eor)) => (None, eor),
+            None => (None, false),
+        };
+        ExportedSilence {
+            id: payload.entry_id(),
+            subscription: payload.res.clone(),
+            check: payload.chk.clone(),
+            creator: Some(payload.creator.clone().unwrap_or_else(current_user)),
+            reason: payload.reason.clone(),
+            expire_on_resolve: eor,
+            begin: payload.begin,
+            expire_at,
+        }
+    }
+
+    /// Payload that recreates the entry at the UNIX timestamp `now` with its original deadline,
+    /// or `None` if the entry has expired by then
+    pub fn into_payload(self, now: i64) -> Option<SensuPayload> {
+        let eor = self.expire_on_resolve;
+        let expire = match self.expire_at {
+            Some(at) if at <= now => return None,
+            Some(at) => Expire::Until(at, eor),
+            None => Expire::NoExpiration(eor),
+        };
+        Some(SensuPayload {
+            res: self.subscription,
+            chk: self.check,
+            expire: Some(expire),
+            reason: self.reason,
+            creator: self.creator,
+            begin: self.begin.filter(|b| *b > now),
+            id: None,
+        })
+    }
+}
+
+impl Export {
+    /// Snapshot the entries as fetched at the UNIX timestamp `now`
+    pub fn new(silences: Vec<Silence>, now: i64) -> Self {
+        Export {
+            version: EXPORT_VERSION,
+            exported_at: now,
+            silences: silences.into_iter().map(|s| ExportedSilence {
+                expire_at: s.expires_at(now),
+                id: s.id,
+                subscription: s.subscription,
+                check: s.check,
+                creator: s.creator,
+                reason: s.reason,
+                expire_on_resolve: s.expire_on_resolve,
+                begin: s.begin,
+            }).collect(),
+        }
+    }
+
+    /// Read a snapshot from a JSON or YAML file
+    pub fn load(path: &str) -> Result<Self, SensuError> {
+        let contents = fs::read_to_string(path).map_err(|e| SensuError::new_string(
+            format_args!("Failed to read export {}: {}", path, e)
+        ))?;
+        let export: Export = serde_yaml::from_str(&contents).map_err(|e| SensuError::new_string(
+            format_args!("Failed to parse export {}: {}", path, e)
+        ))?;
+        if export.version > EXPORT_VERSION {
+            return Err(SensuError::new_string(format_args!(
+                "Export {} has version {} but this version of shush only reads up to version {}",
+                path, export.version, EXPORT_VERSION
+            )));
+        }
+        Ok(export)
+    }
+
+    /// Write the snapshot to a file, as JSON if the file name ends in `.json` and as YAML
+    /// otherwise
+    pub fn save(&self, path: &str) -> Result<(), SensuError> {
+        let json = Path::new(path).extension().map(|e| e == "json").unwrap_or(false);
+        let contents = if json {
+            serde_json::to_string_pretty(self).map_err(SensuError::new_string)?
+        } else {
+            serde_yaml::to_string(self).map_err(SensuError::new_string)?
+        };
+        fs::write(path, contents).map_err(|e| SensuError::new_string(
+            format_args!("Failed to write export {}: {}", path, e)
+        ))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let silences: Vec<Silence> = serde_json::from_str(r#"[
+            {"id": "linux:*", "subscription": "linux", "expire": 600, "creator": "ops",
+             "reason": "patching", "begin": 1499999000},
+            {"id": "*:check_disk", "check": "check_disk", "expire": -1,
+             "expire_on_resolve": true}
+        ]"#).unwrap();
+        let export = Export::new(silences, 1500000000);
+        assert_eq!(export.silences[0].expire_at, Some(1500000600));
+        assert_eq!(export.silences[1].expire_at, None);
+
+        let reread: Export = serde_yaml::from_str(&serde_yaml::to_string(&export).unwrap())
+            .unwrap();
+        assert_eq!(reread, export);
+
+        let mut silences = reread.silences.into_iter();
+        assert_eq!(silences.next().unwrap().into_payload(1500000300), Some(SensuPayload {
+            res: Some("linux".to_string()),
+            expire: Some(Expire::Until(1500000600, false)),
+            reason: Some("patching".to_string()),
+            creator: Some("ops".to_string()),
+            ..SensuPayload::default()
+        }));
+        assert_eq!(silences.next().unwrap().into_payload(1600000000).unwrap().expire,
+                   Some(Expire::NoExpiration(true)));
+    }
+
+    #[test]
+    fn test_expired() {
+        let silence = ExportedSilence {
+            id: "linux:*".to_string(),
+            subscription: Some("linux".to_string()),
+            check: None,
+            creator: None,
+            reason: None,
+            expire_on_resolve: false,
+            begin: None,
+            expire_at: Some(1500000600),
+        };
+        assert_eq!(silence.into_payload(1500000600), None);
+    }
+}
@@ -0,0 +1,162 @@
+//! Local record of the silences each shush invocation created, used to undo invocations
+
+use std::env;
+use std::fs::{self,OpenOptions};
+use std::io::{ErrorKind,Write};
+use std::path::{Path,PathBuf};
+
+use serde_json;
+
+use err::SensuError;
+use export::ExportedSilence;
+use sensu::SensuPayload;
+
+/// Silences created by a single shush invocation
+#[derive(Clone,Debug,Deserialize,PartialEq,Serialize)]
+pub struct Invocation {
+    /// Sequence number of the invocation, starting at 1
+    pub id: u64,
+    /// UNIX timestamp at which the silences were created
+    pub timestamp: i64,
+    /// Command line of the invocation
+    pub command: Vec<String>,
+    /// API the silences were created in
+    #[serde(default)]
+    pub api: Option<String>,
+    /// Created silences
+    pub silences: Vec<ExportedSilence>,
+    /// UNIX timestamp at which the invocation was undone, if it was
+    #[serde(default)]
+    pub undone: Option<i64>,
+}
+
+/// Journal of invocations stored as one JSON object per line
+#[derive(Clone,Debug,PartialEq)]
+pub struct Journal {
+    path: PathBuf,
+}
+
+impl Journal {
+    /// Journal in `~/.shush/journal.jsonl`
+    pub fn open_default() -> Result<Self, SensuError> {
+        let home = env::var("HOME").map_err(|_| SensuError::new(
+            "$HOME environment variable not found - cannot locate ~/.shush/journal.jsonl"
+        ))?;
+        Ok(Journal::new(Path::new(&home).join(".shush").join("journal.jsonl")))
+    }
+
+    /// Journal stored at `path`
+    pub fn new<P>(path: P) -> Self where P: Into<PathBuf> {
+        Journal { path: path.into() }
+    }
+
+    /// All recorded invocations, oldest first - empty if nothing was recorded yet
+    pub fn invocations(&self) -> Result<Vec<Invocation>, SensuError> {
+        let contents = match fs::read_to_string(&self.path) {
+            Ok(c) => c,
+            Err(ref e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(SensuError::new_string(format_args!(
+                "Failed to read journal {}: {}", self.path.display(), e
+            ))),
+        };
+        contents.lines().filter(|l| !l.trim().is_empty()).enumerate().map(|(i, l)| {
+            serde_json::from_str(l).map_err(|e| SensuError::new_string(format_args!(
+                "Failed to parse line {} of journal {}: {}", i + 1, self.path.display(), e
+            )))
+        }).collect()
+    }
+
+    /// Append an invocation that created `payloads` at the UNIX timestamp `now`, returning its
+    /// sequence number
+    pub fn record(&self, command: Vec<String>, api: Option<String>, payloads: &[SensuPayload],
+                  now: i64) -> Result<u64, SensuError> {
+        let id = self.invocations()?.last().map(|i| i.id + 1).unwrap_or(1);
+        let invocation = Invocation {
+            id,
+            timestamp: now,
+            command,
+            api,
+            silences: payloads.iter().map(|p| ExportedSilence::from_payload(p, now)).collect(),
+            undone: None,
+        };
+        let line = serde_json::to_string(&invocation).map_err(SensuError::new_string)?;
+        let write_err = |e| SensuError::new_string(format_args!(
+            "Failed to write journal {}: {}", self.path.display(), e
+        ));
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir).map_err(write_err)?;
+        }
+        let mut file = OpenOptions::new().create(true).append(true).open(&self.path)
+            .map_err(write_err)?;
+        writeln!(file, "{}", line).map_err(write_err)?;
+        Ok(id)
+    }
+
+    /// Invocation with sequence number `id`, or the last invocation that was not undone yet
+    /// if `None`
+    pub fn find(&self, id: Option<u64>) -> Result<Invocation, SensuError> {
+        let invocations = self.invocations()?;
+        match id {
+            Some(id) => invocations.into_iter().find(|i| i.id == id).ok_or_else(|| {
+                SensuError::new_string(format_args!("No invocation {} in the journal", id))
+            }),
+            None => invocations.into_iter().rev().find(|i| i.undone.is_none()).ok_or_else(|| {
+                SensuError::new("No invocation left to undo in the journal")
+            }),
+        }
+    }
+
+    /// Mark the invocation with sequence number `id` as undone at the UNIX timestamp `now`
+    pub fn mark_undone(&self, id: u64, now: i64) -> Result<(), SensuError> {
+        let mut lines = Vec::new();
+        for mut invocation in self.invocations()? {
+            if invocation.id == id {
+                invocation.undone = Some(now);
+            }
+            lines.push(serde_json::to_string(&invocation).map_err(SensuError::new_string)?);
+        }
+        let mut contents = lines.join("\n");
+        contents.push('\n');
+        fs::write(&self.path, contents).map_err(|e| SensuError::new_string(format_args!(
+            "Failed to write journal {}: {}", self.path.display(), e
+        )))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::process;
+
+    use sensu::Expire;
+
+    #[test]
+    fn test_record_and_undo() {
+        let path = env::temp_dir().join(format!("shush-journal-{}.jsonl", process::id()));
+        let _ = fs::remove_file(&path);
+        let journal = Journal::new(path.clone());
+        assert!(journal.invocations().unwrap().is_empty());
+
+        let payload = SensuPayload {
+            res: Some("linux".to_string()),
+            expire: Some(Expire::Expire(3600, false)),
+            creator: Some("ops".to_string()),
+            ..SensuPayload::default()
+        };
+        let command = vec!["shush".to_string(), "-s".to_string(), "linux".to_string()];
+        let payloads = vec![payload];
+        assert_eq!(journal.record(command.clone(), None, &payloads, 1500000000).unwrap(), 1);
+        assert_eq!(journal.record(command, None, &payloads, 1500000100).unwrap(), 2);
+
+        let last = journal.find(None).unwrap();
+        assert_eq!(last.id, 2);
+        assert_eq!(last.silences[0].id, "linux:*");
+        assert_eq!(last.silences[0].expire_at, Some(1500003700));
+        journal.mark_undone(2, 1500000200).unwrap();
+        assert_eq!(journal.find(None).unwrap().id, 1);
+        assert_eq!(journal.find(Some(2)).unwrap().undone, Some(1500000200));
+        assert!(journal.find(Some(3)).is_err());
+
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,418 @@
+//! ## Shush
+//! Silence is golden.
+//!
+//! ### Purpose
+//! Sensu is an alerting solution that provides per host metric checks. The Sensu primitives are checks,
+//! subscriptions, and clients. A check corresponds to a comparison of a metric against the specified
+//! theshold. A client is a single host identified by an ID. A subscription is one or more nodes
+//! grouped under the same subscription name. Sensu can be used for
+//! alerting on critical and warning thresholds and enables integration for any Ruby-compatible
+//! API with many existing plugins that have already been contributed.
+//! As a result this can be a critical part of monitoring infrastucture. However, these thresholds
+//! are static, and in maintenance cases, there can often be false positives and corner cases.
+//! This tool enables the user to create, remove, or list active silences for
+//! combinations of clients/subscriptions and checks in Sensu. This is useful for noise reduction,
+//! scheduled maintenance, and temporary or permanent silencing when adjusting thresholds. Shush
+//! is a simple way to silence on any combination of subscriptions and checks or clients and checks.
+//!
+//! ### External dependencies
+//! A Sensu server with a version of the REST API of 0.29 or greater is a hard requirement.
+//! Sensu Go (2.x+) backends are supported as well by setting `backend = go` in the config file.
+//!
+//! For more information on the Sensu REST API, click
+//! [here](https://sensuapp.org/docs/0.29/api/silenced-api.html). For the Sensu Go API, click
+//! [here](https://docs.sensu.io/sensu-go/latest/api/silenced/).
+//!
+//! ### Configuration
+//! Shush reads an INI file from the path passed to `-f`, `/etc/shush/shush.conf` or
+//! `~/.shush/shush.conf`, in that order. `cfg/shush.conf` in the repository is an example.
+//! The following keys are recognized:
+//!
+//!   * `api` - base URL of the Sensu API
+//!   * `backend` - `legacy` (default) for Sensu 0.29/1.x or `go` for Sensu Go
+//!   * `namespace` - Sensu Go namespace to operate in, defaulting to `default`
+//!   * `ca_bundle` - path to a PEM bundle of CA certificates to trust for `https://` URLs
+//!   * `client_cert` and `client_key` - paths to a PEM client certificate and PKCS #8 key
+//!     for mutual TLS
+//!   * `insecure_skip_verify` - set to `true` to skip server certificate verification;
+//!     only intended for lab clusters with self-signed certificates
+//!   * `user` and `password` - credentials for HTTP basic authentication against Sensu 1.x,
+//!     or for logging in through `/auth` on Sensu Go, after which the access token is
+//!     refreshed automatically
+//!   * `api_key` - Sensu Go API key, used instead of `user` and `password` if set
+//!   * `require_reason` - set to `true` to refuse creating silences without `--reason`
+//!   * `timezone` - IANA time zone such as `Europe/Berlin` that `--begin` and `--until` times
+//!     are interpreted and listed times are shown in, defaulting to the local time zone
+//!   * `client_config` - local Sensu client definition that `--self` reads the client name
+//!     from, defaulting to `/etc/sensu/conf.d/client.json`, or `/etc/sensu/agent.yml` for
+//!     Sensu Go
+//!   * `attributes` - comma separated `ALIAS=PATH` pairs such as
+//!     `gcp=gcp.instance_name,azure=azure.vm_id` that can be passed to `--by` instead of the
+//!     attribute path
+//!
+//! Secrets do not have to be stored in plaintext: `password` and `api_key` can instead be read
+//! from a file with `password_file`/`api_key_file` or from the output of a shell command with
+//! `password_command`/`api_key_command`.
+//!
+//! ### Setup and background
+//! Shush accesses four Sensu API endpoints. For Shush to be operational the following
+//! Sensu endpoints must be reachable:
+//!
+//!   * `GET /clients`
+//!   * `GET /clients/[CLIENT_ID]`
+//!   * `POST /silenced`
+//!   * `POST /silenced/clear`
+//!
+//! When talking to a Sensu Go backend, the equivalent endpoints under
+//! `/api/core/v2/namespaces/[NAMESPACE]` are used instead:
+//!
+//!   * `GET /entities`
+//!   * `GET /entities/[ENTITY_NAME]`
+//!   * `GET /events`
+//!   * `GET /silenced`
+//!   * `POST /silenced`
+//!   * `DELETE /silenced/[SUBSCRIPTION]:[CHECK]`
+//!
+//! Sensu Go has no concept of custom client attributes, so AWS instance IDs are looked up
+//! in the `instance_id` entity label instead.
+//!
+//! This tool gives the user with the option to provide instance IDs (AWS-specific - click [here](#aws-specific-configuration)
+//! for a setup guide) or to provide Sensu client IDs (applicable for all applications using Sensu).
+//!
+//! When providing either instance IDs or Sensu client IDs as opposed to subscriptions, validation
+//! against the Sensu server is performed to verify that the ID is registered and active.
+//! Additionally, shush performs mapping from instance ID to sensu client ID when using
+//! the parameters `-n` (long form, `--aws-nodes`).
+//!
+//! ### Notes on usage
+//! Shush has ten actions: silence, clear silence, list, query, extend, apply, export, import,
+//! undo and history. The default is silence, `-l` enables listing mode, `-r` enables clearing
+//! mode, `-q` enables query mode, `--extend` enables extend mode, `--apply` or `--diff` enable
+//! apply mode, `--export` and `--import` enable export and import mode, and `--undo` and
+//! `--history` enable undo and history mode. `shush exec` wraps a command instead.
+//! 
+//! ### Parameter details
+//! Shush operates in silence mode when neither `-l` nor `-r` is provided.
+//! It will silence any nodes associated
+//! with the instance IDs passed `-n`, client IDs passed to `-i`,
+//! subscriptions passed to `-s`, or checks
+//! passed to `-c`. All arguments can take a single value or a comma separated list of
+//! values. Only one of instance IDs, client IDs and subscriptions can be specified in one
+//! invocation of Shush.
+//!
+//! `--self` can be used instead of `-i` on the host that runs the Sensu client, for example
+//! from reboot hooks. It targets the client named in `client.name` of the local client
+//! definition, or in `name` of the Sensu Go agent config, and falls back to the host name if
+//! neither is set, like both clients do.
+//!
+//! `--by ATTRIBUTE=VALUE1,VALUE2` targets every client whose attribute has one of the values,
+//! the same way `-n` looks clients up by instance ID. The attribute is a dotted path into the
+//! client definition such as `hostname`, `address` or `ec2.instance_id`, or into the entity
+//! on Sensu Go such as `metadata.labels.region`, or an alias from the `attributes` config
+//! option.
+//!
+//! `--where` selects clients with an expression evaluated against every client from
+//! `GET /clients`, such as `environment=staging and role=db`. Attributes are compared with
+//! `=`, `!=`, regex matches `=~` and `!~`, or `in` and `not in` followed by a list like
+//! `(db, cache)`, and comparisons are combined with `and`, `or`, `not` and parentheses (see
+//! [`selector`]). Every matched client is silenced individually, and the matched clients are
+//! listed before silencing or clearing so that the selector can be checked with `--dry-run`.
+//!
+//! `-l` combined with either of the flags `-c` or `-s` will list the requested information
+//! matched against the argument passed to the corresponding flag. This is expected to be a regex
+//! and will be compiled as such or ignored.
+//!
+//! `-l` combined with `-i` or `-n` instead lists every silence that applies to those clients:
+//! silences on the clients themselves, on any of their subscriptions and on all subscriptions.
+//!
+//! Listed silences can be narrowed further with `--creator` (a regex) or `--mine` for silences
+//! created by `$USER`, `--never-expires`, `--expires-within` (a duration such as `4h`) and
+//! `--target client` or `--target subscription`. `--sort expiry` lists the soonest expiring
+//! silences first and `--sort creator` orders them by creator. The `text` and `table` formats
+//! end with a count of the listed silences.
+//!
+//! `-r` added to the same parameters used in silence mode will simply
+//! clear the same checks created by silence mode.
+//! Individual entries can also be cleared with `-r --id` and the IDs shown by `-l`.
+//!
+//! `--extend` reposts existing silences with a new expiration and keeps their creator, reason
+//! and expire on resolve flag. Silences are selected with `--id` or with the same parameters and
+//! filters as `-l`. The new expiration is either `-e` counted from now, `-u`, or `--add` to add
+//! time to the remaining time of each silence.
+//!
+//! `-q` combined with a client from `-i` or `-n` and a check from `-c` reports whether Sensu
+//! would currently suppress that check on that client, and which silences are responsible.
+//! Shush exits with status 0 if the check is silenced, 3 if it is not and 1 on errors.
+//!
+//! `--apply` reads a YAML or JSON manifest of silences (see [`manifest::Manifest`]) and makes the
+//! silences on the targets it names match it: missing entries are created, entries whose
+//! reason, begin, expiration or expire on resolve differ are reposted in place, and other
+//! entries on the same subscriptions and clients are cleared. Entries on targets the manifest
+//! does not name are left alone. `--diff` prints the same changes without making them. If a
+//! request fails, shush prints the changes it made, the one that failed and the ones it did not
+//! get to.
+//!
+//! `--export` writes every silence entry to a versioned snapshot file (see [`export::Export`])
+//! with expirations stored as absolute deadlines. `--import` recreates the entries of a snapshot
+//! with their original creators, reasons and deadlines and skips entries that have expired
+//! since the snapshot was taken.
+//!
+//! Every invocation that creates silences is recorded in `~/.shush/journal.jsonl` along with its
+//! command line and the silences it created. `--history` lists the recorded invocations and
+//! `--undo` clears the silences of the last invocation that was not undone yet, or of the
+//! invocation with the number given to it.
+//!
+//! `shush exec` takes the same `-n`, `-i`, `-s`, `-c` and `--reason` parameters followed by
+//! `--` and a command. The silences are created with a short expiration from `--ttl`
+//! (10 minutes by default) that is renewed while the command runs, and cleared once it exits,
+//! including after SIGINT or SIGTERM, which are passed on to the command. A signal received
+//! before the command starts skips it. Shush exits with the exit status of the command, even if
+//! clearing fails. With `--dry-run` it only prints the silences it would create. If shush
+//! itself is killed, the silences expire on their own after the TTL instead of lingering.
+//!
+//! ### Library usage
+//! Everything the `shush` binary does is available from this crate. The flows in [`actions`]
+//! operate on any [`backend::SilenceBackend`], of which [`SensuClient`] is the Sensu
+//! implementation and [`backend::MemoryBackend`] an in-memory fake for tests. They return
+//! structured results instead of printing so callers can decide how to present them.
+//!
+//! ```no_run
+//! extern crate shush;
+//!
+//! use shush::{actions,ShushResources,ShushResourceType,SilenceOpts,Expire,SensuClient};
+//! use shush::config::ShushConfig;
+//!
+//! let cfg = ShushConfig::new(Some("/etc/shush/shush.conf".to_string())).unwrap();
+//! let mut client = SensuClient::from_config(&cfg).unwrap();
+//! let report = actions::silence(&mut client, SilenceOpts {
+//!     resources: Some(ShushResources {
+//!         res_type: ShushResourceType::Client,
+//!         resources: vec!["host1".to_string()],
+//!     }),
+//!     checks: None,
+//!     expire: Expire::Expire(3600, false),
+//!     reason: Some("kernel upgrade".to_string()),
+//!     begin: None,
+//! }).unwrap();
+//! for payload in report.payloads {
+//!     println!("Silenced {}", payload);
+//! }
+//! ```
+//!
+//! ### Rust Version
+//! Shush is built and tested against the current stable Rust toolchain.
+//!
+//! # Examples
+//! ## List all active silences
+//! ```sh
+//! shush -l
+//! ```
+//! 
+//! ## List all active silences with a subscription matching the regex `something.*`
+//! ```sh
+//! shush -l -s "something.*"
+//! ```
+//!
+//! ## List my silences that expire within the next day, soonest first
+//! ```sh
+//! shush -l --mine --expires-within 1d --sort expiry
+//! ```
+//!
+//! ## List all silences that keep client `CLIENT_1` from alerting
+//! ```sh
+//! shush -l -i CLIENT_1
+//! ```
+//!
+//! ## Check whether `SOME_CHECK` on client with instance ID `INST_ID_1` is silenced
+//! ```sh
+//! shush -q -n INST_ID_1 -c SOME_CHECK && echo "silenced"
+//! ```
+//!
+//! ## List all active silences as JSON for scripts
+//! `--output` also accepts `jsonl`, `yaml`, `csv` and `table`; the default is `text`. The
+//! `text` and `table` formats show the remaining time, expiry time and creation time of each
+//! silence while the machine readable formats keep the raw values from the API.
+//! ```sh
+//! shush -l --output json
+//! ```
+//!
+//! ## Silence all checks on clients with instance IDs `INST_ID_1` and `INST_ID_2`
+//! ```sh
+//! shush -n INST_ID_1,INST_ID2
+//! ```
+//!
+//! ## Silence check `SOME_CHECK` for 1 hour and 30 minutes
+//! ```sh
+//! shush -c SOME_CHECK -e 1h30m
+//! ```
+//!
+//! Durations are numbers, optionally fractional, followed by `w`, `d`, `h`, `m` or `s` or
+//! their long names, so `-e "1.5 hours"` and `-e "1 hour 30 minutes"` are equivalent.
+//! A number without a unit is a number of seconds.
+//!
+//! ## Silence check `SOME_CHECK` indefinitely
+//! ```sh
+//! shush -c SOME_CHECK -e none
+//! ```
+//!
+//! ## Silence check `SOME_CHECK` for 4 hours with a reason shown to others listing silences
+//! ```sh
+//! shush -c SOME_CHECK -e 4h --reason "disk replacement, see OPS-123"
+//! ```
+//!
+//! ## Silence subscription `SUB_1` for a 2 hour maintenance window starting in 3 hours
+//! `--begin` also accepts UNIX timestamps written as `@1700000000`, RFC 3339 timestamps and
+//! local times such as `2024-05-01 22:00`. Silences that have not started yet are listed as
+//! pending. Local times that a daylight saving change skips are rejected.
+//! ```sh
+//! shush -s SUB_1 -b "in 3h" -e 2h
+//! ```
+//!
+//! ## Silence check `SOME_CHECK` until 9 AM tomorrow
+//! `--until` also accepts wall-clock times such as `17:30`, weekdays such as `friday` or
+//! `fri 17:30`, and ISO 8601 timestamps. The deadline is converted to an expiration in
+//! seconds when the silence is created.
+//! ```sh
+//! shush -c SOME_CHECK -u "tomorrow 09:00"
+//! ```
+//!
+//! ## Silence check `SOME_CHECK` until alert resolves
+//! ```sh
+//! shush -c SOME_CHECK -o
+//! ```
+//!
+//! ## Silence check `SOME_CHECK` on client with instance ID `INST_ID_1`
+//! ```sh
+//! shush -n INST_ID_1 -c SOME_CHECK
+//! ```
+//!
+//! ## Silence check `SOME_CHECK` on client with Sensu client name `CLIENT_1`
+//! ```sh
+//! shush -i CLIENT_1 -c SOME_CHECK
+//! ```
+//!
+//! ## Silence check `SOME_CHECK` on client with Sensu subscription `SUB_1`
+//! ```sh
+//! shush -s SUB_1 -c SOME_CHECK
+//! ```
+//!
+//! ## Silence all checks on the local client for 15 minutes
+//! ```sh
+//! shush --self -e 15m --reason reboot
+//! ```
+//!
+//! ## Silence check `SOME_CHECK` on the clients with GCP instance names `VM_1` and `VM_2`
+//! ```sh
+//! shush --by gcp_instance_name=VM_1,VM_2 -c SOME_CHECK
+//! ```
+//!
+//! ## Silence every staging database client that has no dedicated subscription
+//! ```sh
+//! shush --where "environment=staging and role in (db, replica)" --dry-run
+//! shush --where "environment=staging and role in (db, replica)" -e 4h
+//! ```
+//!
+//! ## Clear check silence for `SOME_CHECK` on client with instance ID `INST_ID_1`
+//! ```sh
+//! shush -r -n INST_ID_1 -c SOME_CHECK
+//! ```
+//!
+//! ## Preview silencing check `SOME_CHECK` on subscription `SUB_1` without sending anything
+//! ```sh
+//! shush --dry-run -s SUB_1 -c SOME_CHECK
+//! ```
+//!
+//! ## Give my silences on subscription `SUB_1` another hour
+//! ```sh
+//! shush --extend -s SUB_1 --mine --add 1h
+//! ```
+//!
+//! ## Preview and apply the silences for a maintenance window described in `maintenance.yaml`
+//! ```sh
+//! shush --diff maintenance.yaml
+//! shush --apply maintenance.yaml
+//! ```
+//!
+//! ## Silence check `SOME_CHECK` on client with instance ID `INST_ID_1` during a deploy
+//! ```sh
+//! shush exec -n INST_ID_1 -c SOME_CHECK --reason deploy -- ./deploy.sh
+//! ```
+//!
+//! ## Clear everything the last invocation of shush silenced
+//! ```sh
+//! shush --undo
+//! ```
+//!
+//! ## Back up all silences before a migration and restore them afterwards
+//! ```sh
+//! shush --export silences.yaml
+//! shush --import silences.yaml
+//! ```
+//!
+//! ## Clear the silence entries with IDs `client:CLIENT_1:*` and `SUB_1:SOME_CHECK`
+//! IDs are shown when listing silences.
+//! ```sh
+//! shush -r --id "client:CLIENT_1:*,SUB_1:SOME_CHECK"
+//! ```
+//!
+//! ## AWS-Specific Configuration
+//! To configure AWS support for shush, you will need to make modifications on the Sensu side as
+//! well. Sensu checks operate by sending a JSON payload back to the Sensu server with some
+//! predefined and some arbitrary data. To enable shush selection by AWS instance ID, add an
+//! `instance_id` key with a value equivalent to executing the following command from your AWS node:
+//!
+//! ```sh
+//! curl http://169.254.169.254/1.0/meta-data/instance-id
+//! ```
+//!
+//! This must be added to the Sensu _client_ object. See [here](https://sensuapp.org/docs/0.29/reference/clients.html)
+//! for more details. Once this has been done on the server side, shush will do the rest.
+
+#![deny(missing_docs)]
+
+extern crate base64;
+extern crate chrono;
+extern crate chrono_tz;
+extern crate clap;
+extern crate csv;
+extern crate hyper;
+extern crate hyper_tls;
+extern crate libc;
+extern crate native_tls;
+extern crate regex;
+extern crate tokio;
+
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_json;
+extern crate serde_yaml;
+
+#[macro_use]
+extern crate itertools;
+extern crate ini;
+extern crate nom;
+
+#[cfg(test)]
+extern crate proptest;
+
+pub mod actions;
+pub mod backend;
+pub mod config;
+pub mod err;
+pub mod exec;
+pub mod export;
+pub mod journal;
+pub mod manifest;
+pub mod opts;
+pub mod output;
+pub mod resources;
+pub mod selector;
+pub mod sensu;
+pub mod time;
+
+pub use err::SensuError;
+pub use opts::{ApplyOpts,ClearOpts,ExecOpts,ExtendOpts,ImportOpts,ListOpts,ShushOpts,SilenceOpts};
+pub use output::OutputFormat;
+pub use resources::{ShushResources,ShushResourceType};
+pub use sensu::{Expire,SensuClient,SensuPayload};
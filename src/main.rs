@@ -1,168 +1,364 @@
-//! ## Shush
-//! Silence is golden.
-//!
-//! ### Purpose
-//! Sensu is an alerting solution that provides per host metric checks. The Sensu primitives are checks,
-//! subscriptions, and clients. A check corresponds to a comparison of a metric against the specified
-//! theshold. A client is a single host identified by an ID. A subscription is one or more nodes
-//! grouped under the same subscription name. Sensu can be used for
-//! alerting on critical and warning thresholds and enables integration for any Ruby-compatible
-//! API with many existing plugins that have already been contributed.
-//! As a result this can be a critical part of monitoring infrastucture. However, these thresholds
-//! are static, and in maintenance cases, there can often be false positives and corner cases.
-//! This tool enables the user to create, remove, or list active silences for
-//! combinations of clients/subscriptions and checks in Sensu. This is useful for noise reduction,
-//! scheduled maintenance, and temporary or permanent silencing when adjusting thresholds. Shush
-//! is a simple way to silence on any combination of subscriptions and checks or clients and checks.
-//!
-//! ### External dependencies
-//! A Sensu server with a version of the REST API of 0.29 or greater is a hard requirement.
-//!
-//! For more information on the Sensu REST API, click
-//! [here](https://sensuapp.org/docs/0.29/api/silenced-api.html).
-//!
-//! ### Setup and background
-//! Shush accesses four Sensu API endpoints. For Shush to be operational the following
-//! Sensu endpoints must be reachable:
-//!
-//!   * `GET /clients`
-//!   * `GET /clients/[CLIENT_ID]`
-//!   * `POST /silenced`
-//!   * `POST /silenced/clear`
-//!
-//! This tool gives the user with the option to provide instance IDs (AWS-specific - click [here](#aws-specific-configuration)
-//! for a setup guide) or to provide Sensu client IDs (applicable for all applications using Sensu).
-//!
-//! When providing either instance IDs or Sensu client IDs as opposed to subscriptions, validation
-//! against the Sensu server is performed to verify that the ID is registered and active.
-//! Additionally, shush performs mapping from instance ID to sensu client ID when using
-//! the parameters `-n` (long form, `--aws-nodes`).
-//!
-//! ### Notes on usage
-//! Shush has three actions: silence, clear silence, and list. The default is silence, `-l`
-//! enables listing mode, and `-r` enables clearing mode.
-//! 
-//! ### Parameter details
-//! Shush operates in silence mode when neither `-l` nor `-r` is provided.
-//! It will silence any nodes associated
-//! with the instance IDs passed `-n`, client IDs passed to `-i`,
-//! subscriptions passed to `-s`, or checks
-//! passed to `-c`. All arguments can take a single value or a comma separated list of
-//! values. Only one of instance IDs, client IDs and subscriptions can be specified in one
-//! invocation of Shush.
-//!
-//! `-l` combined with either of the flags `-c` or `-s` (`-n` and `-i` are not allowed)
-//! will list the requested information matched against the argument passed to the
-//! corresponding flag. This is expected to be a regex and will be compiled as such or ignored.
-//!
-//! `-r` added to the same parameters used in silence mode will simply
-//! clear the same checks created by silence mode.
-//!
-//! ### Rust Version
-//! Shush was developed on Rust 1.16 and is not guaranteed to work on anything
-//! earlier.
-//!
-//! # Examples
-//! ## List all active silences
-//! ```
-//! shush -l
-//! ```
-//! 
-//! ## List all active silences with a subscription matching the regex `something.*`
-//! ```
-//! shush -l -s "something.*"
-//! ```
-//!
-//! ## Silence all checks on clients with instance IDs `INST_ID_1` and `INST_ID_2`
-//! ```
-//! shush -n INST_ID_1,INST_ID2
-//! ```
-//!
-//! ## Silence check `SOME_CHECK` for 1 hour and 30 minutes
-//! ```
-//! shush -c SOME_CHECK -e 1h30m
-//! ```
-//!
-//! ## Silence check `SOME_CHECK` indefinitely
-//! ```
-//! shush -c SOME_CHECK -e none
-//! ```
-//!
-//! ## Silence check `SOME_CHECK` until alert resolves
-//! ```
-//! shush -c SOME_CHECK -o
-//! ```
-//!
-//! ## Silence check `SOME_CHECK` on client with instance ID `INST_ID_1`
-//! ```
-//! shush -n INST_ID_1 -c SOME_CHECK
-//! ```
-//!
-//! ## Silence check `SOME_CHECK` on client with Sensu client name `CLIENT_1`
-//! ```
-//! shush -i CLIENT_1 -c SOME_CHECK
-//! ```
-//!
-//! ## Silence check `SOME_CHECK` on client with Sensu subscription `SUB_1`
-//! ```
-//! shush -s SUB_1 -c SOME_CHECK
-//! ```
-//!
-//! ## Clear check silence for `SOME_CHECK` on client with instance ID `INST_ID_1`
-//! ```
-//! shush -r -n INST_ID_1 -c SOME_CHECK
-//! ```
-//!
-//! ## AWS-Specific Configuration
-//! To configure AWS support for shush, you will need to make modifications on the Sensu side as
-//! well. Sensu checks operate by sending a JSON payload back to the Sensu server with some
-//! predefined and some arbitrary data. To enable shush selection by AWS instance ID, add an
-//! `instance_id` key with a value equivalent to executing the following command from your AWS node:
-//!
-//! ```
-//! curl http://169.254.169.254/1.0/meta-data/instance-id
-//! ```
-//!
-//! This must be added to the Sensu _client_ object. See [here](https://sensuapp.org/docs/0.29/reference/clients.html)
-//! for more details. Once this has been done on the server side, shush will do the rest.
-
-#![deny(missing_docs)]
-
-extern crate clap;
-extern crate hyper;
-extern crate regex;
-extern crate tokio;
-
-extern crate serde_json;
-
-#[macro_use]
-extern crate itertools;
-extern crate ini;
-extern crate nom;
-
-mod config;
-mod err;
-mod opts;
-mod resources;
-mod sensu;
+//! Command line interface for shush - see the library documentation for usage
 
+extern crate chrono_tz;
+extern crate shush;
+
+use std::env;
 use std::error::Error;
+use std::io;
+use std::process;
+use std::time::{Duration,SystemTime,UNIX_EPOCH};
+
+use chrono_tz::Tz;
+
+use shush::{actions,exec,output};
+use shush::actions::{ManifestDiff,Plan};
+use shush::backend::SilenceBackend;
+use shush::journal::Journal;
+use shush::opts::Args;
+use shush::{ClearOpts,ExecOpts,SensuClient,SensuError,SensuPayload,ShushOpts,ShushResources,
+            ShushResourceType};
+
+/// Exit status of `--query` if the check is not silenced on every client
+const EXIT_NOT_SILENCED: i32 = 3;
+
+/// Print the requests of a dry run
+fn print_plan(plan: Plan, verb: &str, tz: Option<Tz>) {
+    plan.warnings.iter().for_each(|w| println!("{}", w));
+    println!("Dry run - no requests will be sent");
+    for planned in plan.silences {
+        let begin = planned.payload.begin.map(|b| format!(" starting at {}", output::timestamp(b, tz)))
+            .unwrap_or_default();
+        match planned.payload.expire {
+            Some(ref e) => println!("\nWould {} {}{} and {}", verb, planned.payload, begin,
+                                    e.describe(tz)),
+            None => println!("\nWould {} {}", verb, planned.payload),
+        }
+        println!("{}", planned.request);
+        if let Some(n) = planned.clients {
+            println!("Covers {} client{}", n, if n == 1 { "" } else { "s" });
+        }
+    }
+}
+
+/// Describe what a payload silences, when it begins and when it expires
+fn describe(payload: &SensuPayload, tz: Option<Tz>) -> String {
+    let begin = payload.begin.map(|b| format!(" starting at {}", output::timestamp(b, tz)))
+        .unwrap_or_default();
+    let expire = payload.expire.as_ref().map(|e| format!(" and will {}", e.describe(tz)))
+        .unwrap_or_default();
+    format!("{}{}{}", payload, begin, expire)
+}
 
-use opts::ShushOpts;
-use sensu::SensuClient;
+/// Print the changes made to match a manifest, or that would be made in a dry run
+fn print_diff(diff: ManifestDiff, dry_run: bool, tz: Option<Tz>) {
+    diff.warnings.iter().for_each(|w| println!("{}", w));
+    if dry_run {
+        println!("Dry run - no requests will be sent");
+    }
+    diff.create.iter().for_each(|p| println!("+ {}", describe(&p.payload, tz)));
+    diff.update.iter().for_each(|p| println!("~ {}", describe(&p.payload, tz)));
+    diff.clear.iter().for_each(|p| println!("- {}", p.payload));
+    println!("{} to create, {} to update, {} to clear, {} unchanged", diff.create.len(),
+             diff.update.len(), diff.clear.len(), diff.unchanged.len());
+}
+
+/// Print the clients a `--where` selector matches and target exactly those clients from
+/// then on, so that they are not looked up a second time
+fn preview(client: &mut SensuClient, target: Option<&mut ShushResources>)
+           -> Result<(), SensuError> {
+    let target = match target {
+        Some(t) if t.res_type == ShushResourceType::Selector => t,
+        _ => return Ok(()),
+    };
+    let description = target.resources.iter().map(|s| format!("\"{}\"", s))
+        .collect::<Vec<_>>().join(" or ");
+    let matched = client.resolve_clients(ShushResources {
+        res_type: ShushResourceType::Selector,
+        resources: target.resources.clone(),
+    })?;
+    matched.warnings.iter().for_each(|w| println!("{}", w));
+    let n = matched.items.len();
+    println!("Selector {} matches {} client{}{}", description, n,
+             if n == 1 { "" } else { "s" }, if n == 0 { "" } else { ":" });
+    matched.items.iter().for_each(|c| println!("\t{}", c.name));
+    *target = ShushResources {
+        resources: matched.items.iter().map(|c| c.name.clone()).collect(),
+        res_type: ShushResourceType::Resolved(matched.items),
+    };
+    Ok(())
+}
+
+fn unix_now() -> i64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or(0)
+}
+
+/// Record created silences in the journal for `--undo` - the silences exist either way, so
+/// failing to record them only prints a warning
+fn record(payloads: &[SensuPayload], api: Option<String>) {
+    if payloads.is_empty() {
+        return;
+    }
+    let recorded = Journal::open_default()
+        .and_then(|journal| journal.record(env::args().collect(), api, payloads, unix_now()));
+    if let Err(e) = recorded {
+        println!("Failed to record silences in the journal: {}", e);
+    }
+}
+
+/// Clear the silences held by `shush exec` - a failure only prints a warning so that the exit
+/// code of the command is still passed through
+fn release(client: &mut SensuClient, payloads: &[SensuPayload]) {
+    if payloads.is_empty() {
+        return;
+    }
+    let clear = ClearOpts {
+        resources: None,
+        checks: None,
+        ids: Some(payloads.iter().map(|p| p.entry_id()).collect()),
+    };
+    match actions::clear(client, clear) {
+        Ok(cleared) => {
+            cleared.warnings.iter().for_each(|w| println!("{}", w));
+            for payload in cleared.payloads {
+                println!("Clearing {}", payload);
+            }
+        },
+        Err(e) => {
+            println!("Failed to clear silences: {}", e);
+            let ids = payloads.iter().map(|p| p.entry_id());
+            // Some may have been cleared before the failure
+            let left: Vec<String> = match client.silences() {
+                Ok(active) => ids.filter(|id| active.iter().any(|s| s.id == *id)).collect(),
+                Err(_) => ids.collect(),
+            };
+            if !left.is_empty() {
+                println!("Remove the silences left behind with: shush -r --id {}", left.join(","));
+            }
+        },
+    }
+}
+
+/// Silence the targets of `shush exec`, run its command and clear the silences again,
+/// returning the exit code to pass through
+fn exec(client: &mut SensuClient, opts: ExecOpts) -> Result<i32, SensuError> {
+    let ExecOpts { silence, renew_every, command } = opts;
+    // SIGINT and SIGTERM are caught from before the first silence is created until the last
+    // one is cleared so that none are left behind
+    let signals = exec::Signals::catch();
+    let report = match actions::silence(client, silence) {
+        Ok(report) => report,
+        Err(i) => {
+            release(client, &i.report.payloads);
+            return Err(i.error);
+        },
+    };
+    report.warnings.iter().for_each(|w| println!("{}", w));
+    if report.payloads.is_empty() {
+        return Err(SensuError::new("Nothing was silenced - not running the command"));
+    }
+    for payload in &report.payloads {
+        println!("Silencing {} while {} runs", payload, command.join(" "));
+    }
+    let code = match signals.take() {
+        Some(signal) => {
+            println!("Received signal {} - not running {}", signal, command.join(" "));
+            Ok(128 + signal)
+        },
+        None => {
+            let interval = Duration::from_secs(renew_every as u64);
+            signals.supervise(&command, interval, || {
+                if let Err(err) = actions::renew(client, &report.payloads) {
+                    println!("Failed to renew silences - they will expire unless a later \
+                              renewal succeeds: {}", err);
+                }
+            }).map(exec::exit_code)
+        },
+    };
+    // Clear even if the command could not be run so that no silences are left behind
+    release(client, &report.payloads);
+    drop(signals);
+    code
+}
 
 /// Main function - handle arg parsing and all executable actions
 pub fn main() -> Result<(), Box<dyn Error>> {
-    let shush_args = opts::Args::new();
-    let shush_opts = shush_args.getopts();
-    let shush_cfg = shush_args.getconf();
-    
-    let mut client = SensuClient::new(shush_cfg.get("api").unwrap_or(String::new()))?;
-
-    match shush_opts {
-        ShushOpts::Silence(s) => client.silence(s)?,
-        ShushOpts::Clear(c) => client.clear(c)?,
-        ShushOpts::List(l) => client.list(l)?,
+    let shush_args = Args::new();
+    let setup = shush_args.getconf().and_then(|cfg| {
+        let opts = shush_args.getopts(&cfg)?;
+        let tz = cfg.get_timezone()?;
+        let require_reason = cfg.get_bool("require_reason")?;
+        Ok((cfg, opts, tz, require_reason))
+    });
+    let (shush_cfg, mut shush_opts, tz, require_reason) = setup.unwrap_or_else(|e| {
+        println!("{}", e);
+        process::exit(1);
+    });
+
+    let reasonless = match shush_opts {
+        ShushOpts::Silence(ref s) => s.reason.is_none(),
+        ShushOpts::Apply(ref a) => a.silences.iter().any(|s| s.reason.is_none()),
+        ShushOpts::Exec(ref e) => e.silence.reason.is_none(),
+        _ => false,
+    };
+    if reasonless && require_reason {
+        println!("A reason is required to create silences - pass one with --reason or give \
+                  every manifest entry a reason");
+        process::exit(1);
+    }
+
+    let mut client = SensuClient::from_config(&shush_cfg)?;
+
+    let target = match shush_opts {
+        ShushOpts::Silence(ref mut s) => s.resources.as_mut(),
+        ShushOpts::Clear(ref mut c) => c.resources.as_mut(),
+        ShushOpts::Exec(ref mut e) => e.silence.resources.as_mut(),
+        _ => None,
+    };
+    if let Err(e) = preview(&mut client, target) {
+        println!("{}", e);
+        process::exit(1);
+    }
+
+    let result = match shush_opts {
+        ShushOpts::Silence(s) if shush_args.dry_run() => {
+            actions::plan_silence(&mut client, s).map(|plan| print_plan(plan, "silence", tz))
+        },
+        ShushOpts::Clear(c) if shush_args.dry_run() => {
+            actions::plan_clear(&mut client, c).map(|plan| print_plan(plan, "clear silences on", tz))
+        },
+        ShushOpts::Apply(a) if shush_args.dry_run() => {
+            actions::diff(&mut client, a.silences).map(|diff| print_diff(diff, true, tz))
+        },
+        ShushOpts::Apply(a) => match actions::apply(&mut client, a.silences) {
+            Ok(diff) => {
+                print_diff(diff, false, tz);
+                Ok(())
+            },
+            Err(i) => {
+                let partial = *i.report;
+                print_diff(partial.applied, false, tz);
+                if let Some(failed) = partial.failed {
+                    println!("Failed to apply {}", failed.payload);
+                }
+                let pending = partial.pending;
+                if !(pending.create.is_empty() && pending.update.is_empty() &&
+                     pending.clear.is_empty()) {
+                    println!("Not applied because of the failure:");
+                    print_diff(pending, false, tz);
+                }
+                Err(i.error)
+            },
+        },
+        ShushOpts::Import(i) if shush_args.dry_run() => {
+            actions::plan_import(&mut client, i.export).map(|plan| print_plan(plan, "import", tz))
+        },
+        ShushOpts::Extend(e) if shush_args.dry_run() => {
+            actions::plan_extend(&mut client, e).map(|plan| print_plan(plan, "extend", tz))
+        },
+        ShushOpts::Exec(ExecOpts { silence, command, .. }) if shush_args.dry_run() => {
+            actions::plan_silence(&mut client, silence).map(|plan| {
+                print_plan(plan, "silence", tz);
+                println!("\nWould run {}", command.join(" "));
+            })
+        },
+        ShushOpts::Silence(s) => {
+            let (report, result) = match actions::silence(&mut client, s) {
+                Ok(report) => (report, Ok(())),
+                Err(i) => (i.report, Err(i.error)),
+            };
+            report.warnings.iter().for_each(|w| println!("{}", w));
+            for payload in &report.payloads {
+                let expire = payload.expire.as_ref().map(|e| e.describe(tz)).unwrap_or_default();
+                match payload.begin {
+                    Some(b) => println!("Silencing {} starting at {} and will {}", payload,
+                                        output::timestamp(b, tz), expire),
+                    None => println!("Silencing {} and will {}", payload, expire),
+                }
+            }
+            // Silences created before a failed request exist all the same
+            record(&report.payloads, shush_cfg.get("api"));
+            result
+        },
+        ShushOpts::Extend(e) => actions::extend(&mut client, e).map(|report| {
+            report.warnings.iter().for_each(|w| println!("{}", w));
+            for payload in report.payloads {
+                println!("Extending {}", describe(&payload, tz));
+            }
+        }),
+        ShushOpts::Clear(c) => actions::clear(&mut client, c).map(|report| {
+            report.warnings.iter().for_each(|w| println!("{}", w));
+            for payload in report.payloads {
+                println!("Clearing silences on {}", payload);
+            }
+        }),
+        ShushOpts::Undo(id) => Journal::open_default().and_then(|journal| {
+            let invocation = journal.find(id)?;
+            if let (Some(created), Some(api)) = (invocation.api.as_ref(), shush_cfg.get("api")) {
+                if *created != api {
+                    return Err(SensuError::new_string(format_args!(
+                        "Invocation {} created its silences in {} but the configured API is {}",
+                        invocation.id, created, api
+                    )));
+                }
+            }
+            let clear = ClearOpts {
+                resources: None,
+                checks: None,
+                ids: Some(invocation.silences.iter().map(|s| s.id.clone()).collect()),
+            };
+            if shush_args.dry_run() {
+                return actions::plan_clear(&mut client, clear)
+                    .map(|plan| print_plan(plan, "clear", tz));
+            }
+            let report = actions::clear(&mut client, clear)?;
+            report.warnings.iter().for_each(|w| println!("{}", w));
+            for payload in report.payloads {
+                println!("Clearing {}", payload);
+            }
+            journal.mark_undone(invocation.id, unix_now())
+        }),
+        ShushOpts::History => Journal::open_default().and_then(|journal| {
+            output::write_history(&mut io::stdout(), &journal.invocations()?, tz)
+        }),
+        ShushOpts::Exec(e) => match exec(&mut client, e) {
+            Ok(code) => process::exit(code),
+            Err(e) => Err(e),
+        },
+        ShushOpts::Export(path) => actions::export(&mut client).and_then(|export| {
+            export.save(&path)?;
+            println!("Exported {} silence{} to {}", export.silences.len(),
+                     if export.silences.len() == 1 { "" } else { "s" }, path);
+            Ok(())
+        }),
+        ShushOpts::Import(i) => actions::import(&mut client, i.export).map(|report| {
+            report.warnings.iter().for_each(|w| println!("{}", w));
+            for payload in report.payloads {
+                println!("Importing {}", describe(&payload, tz));
+            }
+        }),
+        ShushOpts::List(l) => {
+            let format = l.output;
+            actions::list(&mut client, l).and_then(|silences| {
+                silences.warnings.iter().for_each(|w| eprintln!("{}", w));
+                output::write_silences(&mut io::stdout(), &silences.items, format, tz)
+            })
+        },
+        ShushOpts::Query(q) => {
+            let format = q.output;
+            actions::query(&mut client, q).and_then(|coverage| {
+                coverage.warnings.iter().for_each(|w| eprintln!("{}", w));
+                output::write_coverage(&mut io::stdout(), &coverage.items, format, tz)?;
+                if !coverage.items.iter().all(|c| c.silenced) {
+                    process::exit(EXIT_NOT_SILENCED);
+                }
+                Ok(())
+            })
+        },
     };
+    if let Err(e) = result {
+        println!("{}", e);
+        process::exit(1);
+    }
     Ok(())
 }
@@ -0,0 +1,169 @@
+//! Declarative descriptions of the silences that should exist, read from YAML or JSON
+
+use std::fs;
+
+use chrono::{DateTime,TimeZone};
+use serde_yaml;
+
+use err::SensuError;
+use opts::{get_begin,get_expiration,get_until,SilenceOpts};
+use resources::{ShushResources,ShushResourceType};
+use sensu::Expire;
+
+/// Silences that should exist for the targets they name
+///
+/// ```yaml
+/// silences:
+///   - subscriptions: [db]
+///     checks: [check_disk, check_load]
+///     begin: "2024-05-01 22:00"
+///     until: "2024-05-02 02:00"
+///     reason: database maintenance
+///   - nodes: [i-0123456789abcdef0]
+///     expire: 4h
+///     expire_on_resolve: true
+/// ```
+#[derive(Clone,Debug,Default,Deserialize,PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    /// Silences to create
+    #[serde(default)]
+    pub silences: Vec<ManifestSilence>,
+}
+
+/// A single manifest entry, silencing every combination of its targets and checks
+#[derive(Clone,Debug,Default,Deserialize,PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct ManifestSilence {
+    /// AWS instance IDs to silence
+    pub nodes: Option<Vec<String>>,
+    /// Sensu client names to silence
+    pub clients: Option<Vec<String>>,
+    /// Subscriptions to silence
+    pub subscriptions: Option<Vec<String>>,
+    /// Checks to silence - all checks if `None`
+    pub checks: Option<Vec<String>>,
+    /// Duration such as `2h` or `none` - defaults to 2 hours like the command line
+    pub expire: Option<String>,
+    /// Time the silences expire at, as accepted by `--until`
+    pub until: Option<String>,
+    /// Whether the silences are cleared when the check resolves
+    #[serde(default)]
+    pub expire_on_resolve: bool,
+    /// Time the silences take effect, as accepted by `--begin`
+    pub begin: Option<String>,
+    /// Why the silences are created
+    pub reason: Option<String>,
+}
+
+impl ManifestSilence {
+    /// Convert into the options of a silence action, interpreting times relative to `now`
+    fn into_opts<T>(self, now: &DateTime<T>) -> Result<SilenceOpts, SensuError>
+            where T: TimeZone {
+        let targets = vec![(self.nodes, ShushResourceType::Node),
+                           (self.clients, ShushResourceType::Client),
+                           (self.subscriptions, ShushResourceType::Sub)];
+        let mut targets = targets.into_iter()
+            .filter_map(|(res, res_type)| res.map(|resources| ShushResources { res_type, resources }));
+        let resources = targets.next();
+        if targets.next().is_some() {
+            return Err(SensuError::new("Only one of nodes, clients and subscriptions can be \
+                                        given per manifest entry"));
+        }
+        if resources.is_none() && self.checks.is_none() {
+            return Err(SensuError::new("Manifest entries need targets, checks or both"));
+        }
+
+        let begin = match self.begin {
+            Some(ref b) => Some(get_begin(b, now)?),
+            None => None,
+        };
+        let expire = match (self.expire, self.until) {
+            (Some(_), Some(_)) => {
+                return Err(SensuError::new("Only one of expire and until can be given per \
+                                            manifest entry"));
+            },
+            (_, Some(u)) => Expire::Until(get_until(&u, begin, now)?, self.expire_on_resolve),
+            (e, None) => get_expiration(e.unwrap_or_else(|| "2h".to_string()),
+                                        self.expire_on_resolve)?,
+        };
+        Ok(SilenceOpts {
+            resources,
+            checks: self.checks,
+            expire,
+            reason: self.reason,
+            begin,
+        })
+    }
+}
+
+impl Manifest {
+    /// Read a manifest from a YAML or JSON file
+    pub fn load(path: &str) -> Result<Self, SensuError> {
+        let contents = fs::read_to_string(path).map_err(|e| SensuError::new_string(
+            format_args!("Failed to read manifest {}: {}", path, e)
+        ))?;
+        serde_yaml::from_str(&contents).map_err(|e| SensuError::new_string(
+            format_args!("Failed to parse manifest {}: {}", path, e)
+        ))
+    }
+
+    /// Convert every entry into the options of a silence action, interpreting times relative
+    /// to `now`
+    pub fn into_silences<T>(self, now: &DateTime<T>) -> Result<Vec<SilenceOpts>, SensuError>
+            where T: TimeZone {
+        self.silences.into_iter().enumerate().map(|(i, s)| {
+            s.into_opts(now).map_err(|e| SensuError::new_string(
+                format_args!("Manifest entry {}: {}", i + 1, e)
+            ))
+        }).collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::Utc;
+
+    #[test]
+    fn test_into_silences() {
+        // Friday 2017-07-14 02:40:00 UTC
+        let now = Utc.timestamp_opt(1500000000, 0).unwrap();
+        let manifest: Manifest = serde_yaml::from_str(r#"
+silences:
+  - subscriptions: [db]
+    checks: [check_disk]
+    begin: in 1h
+    until: "2017-07-14 06:00"
+    reason: maintenance
+  - checks: [check_load]
+    expire: 30m
+    expire_on_resolve: true
+"#).unwrap();
+        let silences = manifest.into_silences(&now).unwrap();
+        assert_eq!(silences[0].resources, Some(ShushResources {
+            res_type: ShushResourceType::Sub,
+            resources: vec!["db".to_string()],
+        }));
+        assert_eq!(silences[0].begin, Some(1500003600));
+        assert_eq!(silences[0].expire, Expire::Until(1500012000, false));
+        assert_eq!(silences[0].reason, Some("maintenance".to_string()));
+        assert_eq!(silences[1].resources, None);
+        assert_eq!(silences[1].expire, Expire::Expire(1800, true));
+    }
+
+    #[test]
+    fn test_invalid_entries() {
+        let now = Utc.timestamp_opt(1500000000, 0).unwrap();
+        let invalid = |yaml: &str| {
+            let manifest: Manifest = serde_yaml::from_str(yaml).unwrap();
+            manifest.into_silences(&now).unwrap_err().to_string()
+        };
+        assert!(invalid("silences: [{nodes: [i-abc], clients: [host1]}]")
+                .starts_with("Manifest entry 1: Only one of nodes"));
+        assert!(invalid("silences: [{expire: 1h}]").contains("need targets"));
+        assert!(invalid("silences: [{checks: [a], expire: 1h, until: '17:00'}]")
+                .contains("Only one of expire and until"));
+        assert!(serde_yaml::from_str::<Manifest>("silences: [{check: [a]}]").is_err());
+    }
+}
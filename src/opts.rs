@@ -1,217 +1,954 @@
 //! Generates Shush data structures for `sensu` module from command line flags
 
-use std::process;
-
-use clap::{App,Arg,ArgMatches};
-use regex::Regex;
+use chrono::{DateTime,Local,TimeZone,Utc};
+use clap::{App,Arg,ArgGroup,ArgMatches,SubCommand};
 
 use config::ShushConfig;
+use err::SensuError;
+use export::Export;
+use manifest::Manifest;
+use output::{OutputFormat,OUTPUT_FORMATS};
 use resources::{ShushResources,ShushResourceType};
-use sensu::Expire;
+use regex;
+use selector::Selector;
+
+use sensu::{self,Expire,SensuApi};
+use time;
 
+/// Options for creating silences
+#[derive(Debug)]
 pub struct SilenceOpts {
+    /// Targets to silence - all targets if `None`
     pub resources: Option<ShushResources>,
+    /// Checks to silence - all checks if `None`
     pub checks: Option<Vec<String>>,
+    /// Duration of the silences
     pub expire: Expire,
+    /// Why the silences are being created
+    pub reason: Option<String>,
+    /// UNIX timestamp at which the silences take effect - immediately if `None`
+    pub begin: Option<i64>,
 }
 
+/// Options for clearing silences
 pub struct ClearOpts {
+    /// Targets to clear silences on - all targets if `None`
     pub resources: Option<ShushResources>,
+    /// Checks to clear silences on - all checks if `None`
     pub checks: Option<Vec<String>>,
+    /// IDs of silence entries to clear - used instead of targets and checks if set
+    pub ids: Option<Vec<String>>,
 }
 
+/// Kind of target a silence entry applies to
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum TargetType {
+    /// A single client through its implicit per-client subscription
+    Client,
+    /// A subscription or all subscriptions
+    Subscription,
+}
+
+/// Order to list silences in
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum ListSort {
+    /// Soonest expiring first, entries without expiration last
+    Expiry,
+    /// Alphabetically by creator, entries without creator last
+    Creator,
+}
+
+/// Restrictions on listed silences beyond subscription and check
+#[derive(Clone,Debug,Default,PartialEq)]
+pub struct ListFilter {
+    /// Regex to match creators against
+    pub creator: Option<String>,
+    /// Only list silences without expiration
+    pub never_expires: bool,
+    /// Only list silences expiring within this many seconds
+    pub expires_within: Option<usize>,
+    /// Only list silences on this kind of target
+    pub target: Option<TargetType>,
+}
+
+/// Options for listing silences
+#[derive(Default)]
 pub struct ListOpts {
+    /// Only list silences that apply to these clients
+    pub clients: Option<ShushResources>,
+    /// Regex to match subscriptions against
     pub sub: Option<String>,
+    /// Regex to match checks against
     pub chk: Option<String>,
+    /// Further restrictions on the listed silences
+    pub filter: ListFilter,
+    /// Order to list silences in - as returned by the API if `None`
+    pub sort: Option<ListSort>,
+    /// Format to print the silences in
+    pub output: OutputFormat,
+}
+
+/// New expiration of extended silences
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum Extension {
+    /// Expire this many seconds from now, or from the begin time if it lies in the future
+    For(usize),
+    /// Expire at this UNIX timestamp
+    Until(i64),
+    /// Add this many seconds to the remaining time
+    Add(usize),
+    /// Never expire
+    Never,
+}
+
+/// Options for extending existing silences
+pub struct ExtendOpts {
+    /// Filters selecting the silences to extend - the subscription and check regexes have to
+    /// match the whole subscription or check name
+    pub selection: ListOpts,
+    /// IDs of silences to extend - used instead of the filters if set
+    pub ids: Option<Vec<String>>,
+    /// New expiration
+    pub extension: Extension,
+}
+
+/// Options for checking whether a check on a client is silenced
+pub struct QueryOpts {
+    /// Client to check, by name or instance ID
+    pub client: ShushResources,
+    /// Checks to look up
+    pub checks: Vec<String>,
+    /// Format to print the matching silences in
+    pub output: OutputFormat,
+}
+
+/// Options for bringing silences in line with a manifest
+pub struct ApplyOpts {
+    /// Silences the manifest describes
+    pub silences: Vec<SilenceOpts>,
+}
+
+/// Options for recreating the silences in a snapshot
+pub struct ImportOpts {
+    /// Snapshot to recreate silences from
+    pub export: Export,
 }
 
+/// Options for holding silences while a command runs
+pub struct ExecOpts {
+    /// Silences to hold, expiring after the safety TTL unless renewed
+    pub silence: SilenceOpts,
+    /// Seconds between renewals of the silences
+    pub renew_every: usize,
+    /// Command to run and its arguments
+    pub command: Vec<String>,
+}
+
+/// Action to take and its options
 pub enum ShushOpts {
+    /// Create silences
     Silence(SilenceOpts),
+    /// Clear silences
     Clear(ClearOpts),
+    /// List silences
     List(ListOpts),
+    /// Check whether a check on a client is silenced
+    Query(QueryOpts),
+    /// Repost existing silences with a new expiration
+    Extend(ExtendOpts),
+    /// Create, recreate and clear silences to match a manifest
+    Apply(ApplyOpts),
+    /// Write a snapshot of all silences to the file at the path
+    Export(String),
+    /// Recreate the silences in a snapshot
+    Import(ImportOpts),
+    /// Clear the silences created by the invocation with this journal number, or by the last
+    /// invocation that was not undone yet if `None`
+    Undo(Option<u64>),
+    /// List the invocations in the journal
+    History,
+    /// Silence while a command runs and clear the silences once it exits
+    Exec(ExecOpts),
+}
+
+/// Parse the duration of a silence, which has to be at least a second
+fn get_silence_duration(duration: &str) -> Result<usize, SensuError> {
+    match time::parse_duration(duration)? {
+        0 => Err(SensuError::new_string(format_args!(
+            "Duration \"{}\" is zero - silences have to last at least a second", duration
+        ))),
+        secs => Ok(secs),
+    }
 }
 
-pub fn get_expiration(expire: String, expire_on_resolve: bool) -> Expire {
+/// Parse expiration argument into `Expire`
+pub fn get_expiration(expire: String, expire_on_resolve: bool) -> Result<Expire, SensuError> {
     if expire.as_str() == "none" {
-        return Expire::NoExpiration(expire_on_resolve);
-    }
-    let regex = Regex::new("(?P<num>[0-9]+)(?P<units>[dhms])?").unwrap_or_else(|e| {
-        println!("Failed to compile regex: {}", e);
-        process::exit(1);
-    });
-    let num_secs = regex.captures_iter(expire.as_str()).fold(0, |acc, cap| {
-        let num = cap.name("num").map(|val| val.as_str().parse::<usize>().unwrap_or(0));
-        let units = cap.name("units").map(|val| val.as_str());
-        acc + match (num, units) {
-            (Some(n), Some("d")) => n * 60 * 60 * 24,
-            (Some(n), Some("h")) => n * 60 * 60,
-            (Some(n), Some("m")) => n * 60,
-            (Some(n), Some("s")) => n,
-            (Some(n), None) => n,
-            _ => 60 * 60 * 2,
+        return Ok(Expire::NoExpiration(expire_on_resolve));
+    }
+    Ok(Expire::Expire(get_silence_duration(expire.as_str())?, expire_on_resolve))
+}
+
+/// Parse begin argument into a UNIX timestamp - accepts a relative offset such as `in 3h` or
+/// anything `time::parse_time` accepts
+pub fn get_begin<T>(begin: &str, now: &DateTime<T>) -> Result<i64, SensuError> where T: TimeZone {
+    match begin.trim().strip_prefix("in ") {
+        Some(offset) => now.timestamp().checked_add(time::parse_duration(offset.trim())? as i64)
+            .ok_or_else(|| SensuError::new_string(format_args!(
+                "Time \"{}\" is too far in the future", begin
+            ))),
+        None => time::parse_time(begin, now),
+    }
+}
+
+/// Parse until argument into a UNIX timestamp that must lie after `now` and `begin`
+pub fn get_until<T>(until: &str, begin: Option<i64>, now: &DateTime<T>) -> Result<i64, SensuError>
+        where T: TimeZone {
+    let ts = get_begin(until, now)?;
+    if ts <= now.timestamp() {
+        return Err(SensuError::new_string(format_args!(
+            "Until time \"{}\" is in the past", until
+        )));
+    }
+    if begin.map(|b| ts <= b).unwrap_or(false) {
+        return Err(SensuError::new_string(format_args!(
+            "Until time \"{}\" is not after the begin time", until
+        )));
+    }
+    Ok(ts)
+}
+
+/// Parse begin and until arguments relative to `now`
+fn get_schedule<T>(begin: Option<String>, until: Option<String>, now: &DateTime<T>)
+                   -> Result<(Option<i64>, Option<i64>), SensuError> where T: TimeZone {
+    let begin = match begin {
+        Some(b) => Some(get_begin(&b, now)?),
+        None => None,
+    };
+    let until = match until {
+        Some(u) => Some(get_until(&u, begin, now)?),
+        None => None,
+    };
+    Ok((begin, until))
+}
+
+/// Local client as a target, named by the client definition in the `client_config` config
+/// option or the default location for the configured backend
+fn get_self(cfg: &ShushConfig) -> Result<ShushResources, SensuError> {
+    let api = SensuApi::new(cfg.get("backend"), cfg.get("namespace"))?;
+    let name = sensu::local_client_name(&api, cfg.get("client_config"))?;
+    Ok(ShushResources { res_type: ShushResourceType::Client, resources: vec![name] })
+}
+
+/// JSON pointer to a client attribute given as a dotted path such as `ec2.instance_id` - paths
+/// that already start with `/` are taken as pointers
+pub fn attribute_pointer(path: &str) -> String {
+    if path.starts_with('/') {
+        path.to_string()
+    } else {
+        format!("/{}", path.replace('.', "/"))
+    }
+}
+
+/// Parse a `--by` argument of the form `ATTRIBUTE=VALUE1,VALUE2`, where `ATTRIBUTE` is either
+/// an alias from the comma separated `ALIAS=PATH` pairs in `aliases` or a dotted path
+pub fn get_lookup(by: &str, aliases: Option<String>) -> Result<ShushResources, SensuError> {
+    let invalid = || SensuError::new_string(format_args!(
+        "--by takes an attribute and values such as ec2.instance_id=i-123,i-456, found {}", by
+    ));
+    let mut split = by.splitn(2, '=');
+    let attribute = split.next().map(|a| a.trim()).filter(|a| !a.is_empty())
+        .ok_or_else(invalid)?;
+    let resources: Vec<String> = split.next().ok_or_else(invalid)?.split(',')
+        .map(|v| v.trim().to_string()).filter(|v| !v.is_empty()).collect();
+    if resources.is_empty() {
+        return Err(invalid());
+    }
+    let path = aliases.unwrap_or_default().split(',').filter_map(|pair| {
+        let mut split = pair.splitn(2, '=');
+        match (split.next(), split.next()) {
+            (Some(alias), Some(path)) if alias.trim() == attribute => Some(path.trim().to_string()),
+            _ => None,
         }
-    });
-    Expire::Expire(num_secs, expire_on_resolve)
+    }).next().unwrap_or_else(|| attribute.to_string());
+    Ok(ShushResources {
+        res_type: ShushResourceType::Attribute(attribute_pointer(&path)),
+        resources,
+    })
+}
+
+/// Client target from `--self`, `--by` or `--where`, if any of them was given
+fn get_client_target(matches: &ArgMatches, cfg: &ShushConfig)
+                     -> Result<Option<ShushResources>, SensuError> {
+    if matches.is_present("self") {
+        get_self(cfg).map(Some)
+    } else if let Some(by) = matches.value_of("by") {
+        get_lookup(by, cfg.get("attributes")).map(Some)
+    } else if let Some(selector) = matches.value_of("where") {
+        Selector::parse(selector).map(|_| Some(ShushResources {
+            res_type: ShushResourceType::Selector,
+            resources: vec![selector.to_string()],
+        }))
+    } else {
+        Ok(None)
+    }
 }
 
+/// Arguments that describe silences themselves and cannot be combined with a manifest or
+/// snapshot file
+const FILE_CONFLICTS: &[&str] = &[
+    "nodes", "ids", "subscriptions", "self", "by", "where", "checks", "silenceids", "remove",
+    "list", "query", "expire", "until", "expireonresolve", "begin", "reason", "extend",
+];
+
+/// Arguments selecting the targets and checks of silences, shared by the top level and
+/// `exec`
+fn target_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![Arg::with_name("nodes")
+         .short("n")
+         .long("aws-nodes")
+         .value_name("NODE1,NODE2,...")
+         .help("Comma separated list of instance IDs")
+         .takes_value(true)
+         .conflicts_with_all(&["ids", "subscriptions", "self", "by", "where"]),
+         Arg::with_name("ids")
+         .short("i")
+         .long("client-ids")
+         .value_name("ID1,ID2,...")
+         .help("Comma separated list of client IDs")
+         .takes_value(true)
+         .conflicts_with_all(&["nodes", "subscriptions", "self", "by", "where"]),
+         Arg::with_name("subscriptions")
+         .short("s")
+         .long("subscriptions")
+         .value_name("SUB1,SUB2,...")
+         .help("Comma separated list of subscriptions")
+         .takes_value(true)
+         .conflicts_with_all(&["nodes", "ids", "self", "by", "where"]),
+         Arg::with_name("self")
+         .long("self")
+         .help("Target the local client, named in the local Sensu client definition or agent \
+                config")
+         .takes_value(false)
+         .conflicts_with_all(&["nodes", "ids", "subscriptions", "by", "where"]),
+         Arg::with_name("by")
+         .long("by")
+         .value_name("ATTRIBUTE=VALUE1,VALUE2,...")
+         .help("Target the clients whose attribute has one of the values - the attribute is a \
+                dotted path such as \"ec2.instance_id\" or an alias from the attributes config \
+                option")
+         .takes_value(true)
+         .conflicts_with_all(&["nodes", "ids", "subscriptions", "self", "where"]),
+         Arg::with_name("where")
+         .long("where")
+         .value_name("SELECTOR")
+         .help("Target the clients matching a selector such as \"environment=staging and role \
+                in (db, cache)\" - supports =, !=, =~, !~, in, not in, and, or, not and \
+                parentheses on client attributes")
+         .takes_value(true)
+         .conflicts_with_all(&["nodes", "ids", "subscriptions", "self", "by"]),
+         Arg::with_name("checks")
+         .short("c")
+         .long("checks")
+         .takes_value(true)
+         .help("Comma separated list of checks")
+         .value_name("CHK1,CHK2,...")]
+}
+
+/// Parsed command line arguments
 pub struct Args<'a>(clap::ArgMatches<'a>);
 
 impl<'a> Args<'a> {
+    /// Parse arguments of the current process
+    #[allow(clippy::new_without_default)]
     pub fn new() -> Self {
         Args(App::new("shush").version(env!("CARGO_PKG_VERSION"))
             .author("John Baublitz")
             .about("Sensu silencing tool")
-            .arg(Arg::with_name("nodes")
-                 .short("n")
-                 .long("aws-nodes")
-                 .value_name("NODE1,NODE2,...")
-                 .help("Comma separated list of instance IDs")
-                 .takes_value(true)
-                 .conflicts_with_all(&["ids", "subscriptions"]))
-            .arg(Arg::with_name("ids")
-                 .short("i")
-                 .long("client-ids")
-                 .value_name("ID1,ID2,...")
-                 .help("Comma separated list of client IDs")
-                 .takes_value(true)
-                 .conflicts_with_all(&["nodes", "subscriptions"]))
-            .arg(Arg::with_name("subscriptions")
-                 .short("s")
-                 .long("subscriptions")
-                 .value_name("SUB1,SUB2,...")
-                 .help("Comma separated list of subscriptions")
-                 .takes_value(true)
-                 .conflicts_with_all(&["nodes", "ids"]))
+            .args(&target_args())
             .arg(Arg::with_name("remove")
                  .short("r")
                  .long("remove")
                  .takes_value(false)
                  .help("Remove specified silences"))
+            .arg(Arg::with_name("silenceids")
+                 .long("id")
+                 .value_name("ID1,ID2,...")
+                 .help("Comma separated list of silence IDs to remove, as shown when listing")
+                 .takes_value(true)
+                 .requires("modify")
+                 .conflicts_with_all(&["nodes", "ids", "subscriptions", "self", "by", "where",
+                                       "checks"]))
             .arg(Arg::with_name("list")
                  .short("l")
                  .long("list")
                  .takes_value(false)
-                 .help("List silences")
-                 .conflicts_with("nodes"))
-            .arg(Arg::with_name("checks")
-                 .short("c")
-                 .long("checks")
-                 .takes_value(true)
-                 .help("Comma separated list of checks")
-                 .value_name("CHK1,CHK2,..."))
+                 .help("List silences"))
             .arg(Arg::with_name("expire")
                  .short("e")
                  .long("expire")
-                 .help("Time until check should expire or \"none\" for unlimited TTL")
+                 .help("Time until check should expire, such as \"1h30m\" or \"2 days\", or \"none\" \
+                        for unlimited TTL")
                  .takes_value(true)
                  .value_name("EXPIRATION_TTL"))
+            .arg(Arg::with_name("until")
+                 .short("u")
+                 .long("until")
+                 .help("When the silence should expire, such as \"17:30\", \"tomorrow 09:00\", \
+                        \"friday\" or an ISO 8601 timestamp")
+                 .takes_value(true)
+                 .value_name("UNTIL")
+                 .conflicts_with_all(&["expire", "remove", "list"]))
             .arg(Arg::with_name("expireonresolve")
                  .short("o")
                  .long("expire-on-resolve")
                  .help("On resolution of alert, clear silence")
                  .takes_value(false))
+            .arg(Arg::with_name("begin")
+                 .short("b")
+                 .long("begin")
+                 .help("When the silence takes effect, such as \"in 3h\" or \"2024-05-01 22:00\"")
+                 .value_name("BEGIN")
+                 .takes_value(true)
+                 .conflicts_with_all(&["remove", "list"]))
+            .arg(Arg::with_name("reason")
+                 .long("reason")
+                 .help("Reason for the silence, shown when listing silences")
+                 .value_name("REASON")
+                 .takes_value(true)
+                 .conflicts_with_all(&["remove", "list"]))
             .arg(Arg::with_name("configfile")
                  .short("f")
                  .long("config-file")
                  .help("Path to INI config file")
                  .value_name("FILE_PATH")
-                 .takes_value(true))
+                 .takes_value(true)
+                 .global(true))
+            .arg(Arg::with_name("dryrun")
+                 .long("dry-run")
+                 .help("Print the requests that would be sent without sending them")
+                 .takes_value(false)
+                 .conflicts_with("list"))
+            .arg(Arg::with_name("output")
+                 .long("output")
+                 .help("Output format for listing or querying silences")
+                 .value_name("FORMAT")
+                 .possible_values(OUTPUT_FORMATS)
+                 .requires("read"))
+            .arg(Arg::with_name("query")
+                 .short("q")
+                 .long("query")
+                 .help("Check whether checks on a client are silenced - exits with 0 if all of \
+                        them are and 3 if any is not")
+                 .takes_value(false)
+                 .requires("checks")
+                 .conflicts_with_all(&["remove", "dryrun"]))
+            .arg(Arg::with_name("creator")
+                 .long("creator")
+                 .help("Regex to match the creator of listed silences against")
+                 .value_name("REGEX")
+                 .takes_value(true)
+                 .requires("select"))
+            .arg(Arg::with_name("mine")
+                 .long("mine")
+                 .help("Only list silences created by the current user")
+                 .takes_value(false)
+                 .requires("select")
+                 .conflicts_with("creator"))
+            .arg(Arg::with_name("neverexpires")
+                 .long("never-expires")
+                 .help("Only list silences without expiration")
+                 .takes_value(false)
+                 .requires("select"))
+            .arg(Arg::with_name("expireswithin")
+                 .long("expires-within")
+                 .help("Only list silences expiring within a duration such as \"1h\" or \"2 days\"")
+                 .value_name("DURATION")
+                 .takes_value(true)
+                 .requires("select")
+                 .conflicts_with("neverexpires"))
+            .arg(Arg::with_name("target")
+                 .long("target")
+                 .help("Only list silences on single clients or on subscriptions")
+                 .value_name("TARGET")
+                 .possible_values(&["client", "subscription"])
+                 .requires("select"))
+            .arg(Arg::with_name("sort")
+                 .long("sort")
+                 .help("Order to list silences in")
+                 .value_name("KEY")
+                 .possible_values(&["expiry", "creator"])
+                 .requires("select"))
+            .arg(Arg::with_name("apply")
+                 .long("apply")
+                 .help("Create, recreate and clear silences on the targets in a YAML or JSON \
+                        manifest until they match it")
+                 .value_name("MANIFEST")
+                 .takes_value(true)
+                 .conflicts_with_all(FILE_CONFLICTS))
+            .arg(Arg::with_name("diff")
+                 .long("diff")
+                 .help("Print the changes --apply would make for a manifest without making them")
+                 .value_name("MANIFEST")
+                 .takes_value(true)
+                 .conflicts_with_all(FILE_CONFLICTS)
+                 .conflicts_with("apply"))
+            .arg(Arg::with_name("export")
+                 .long("export")
+                 .help("Write all silences to a versioned snapshot file - JSON if the file name \
+                        ends in .json and YAML otherwise")
+                 .value_name("FILE")
+                 .takes_value(true)
+                 .conflicts_with_all(FILE_CONFLICTS)
+                 .conflicts_with_all(&["apply", "diff", "import", "dryrun"]))
+            .arg(Arg::with_name("import")
+                 .long("import")
+                 .help("Recreate the silences in a snapshot file with their original deadlines, \
+                        skipping expired ones")
+                 .value_name("FILE")
+                 .takes_value(true)
+                 .conflicts_with_all(FILE_CONFLICTS)
+                 .conflicts_with_all(&["apply", "diff"]))
+            .arg(Arg::with_name("extend")
+                 .long("extend")
+                 .help("Repost the silences selected by --id or the listing filters with the \
+                        expiration from --expire, --until or --add, keeping their creator, reason \
+                        and expire on resolve flag")
+                 .takes_value(false)
+                 .conflicts_with_all(&["query", "begin", "reason", "expireonresolve"]))
+            .arg(Arg::with_name("extendby")
+                 .long("add")
+                 .help("Time to add to the remaining time of extended silences, such as \"1h\"")
+                 .value_name("DURATION")
+                 .takes_value(true)
+                 .requires("extend")
+                 .conflicts_with_all(&["expire", "until"]))
+            .arg(Arg::with_name("undo")
+                 .long("undo")
+                 .help("Clear the silences created by the last invocation, or by the invocation \
+                        with the given number from --history")
+                 .value_name("INVOCATION")
+                 .takes_value(true)
+                 .min_values(0)
+                 .max_values(1)
+                 .conflicts_with_all(FILE_CONFLICTS)
+                 .conflicts_with_all(&["apply", "diff", "export", "import"]))
+            .arg(Arg::with_name("history")
+                 .long("history")
+                 .help("List past invocations that created silences, as recorded in \
+                        ~/.shush/journal.jsonl")
+                 .takes_value(false)
+                 .conflicts_with_all(FILE_CONFLICTS)
+                 .conflicts_with_all(&["apply", "diff", "export", "import", "undo", "dryrun"]))
+            .subcommand(SubCommand::with_name("exec")
+                 .about("Silence while a command runs, renewing the silences until it exits and \
+                         clearing them afterwards - exits with the exit code of the command")
+                 .args(&target_args())
+                 .arg(Arg::with_name("reason")
+                      .long("reason")
+                      .help("Reason for the silence, shown when listing silences")
+                      .value_name("REASON")
+                      .takes_value(true))
+                 .arg(Arg::with_name("ttl")
+                      .long("ttl")
+                      .help("Expiration of the silences if shush cannot clear them, such as \
+                             \"10m\" - renewed every third of it while the command runs")
+                      .value_name("DURATION")
+                      .takes_value(true)
+                      .default_value("10m"))
+                 .arg(Arg::with_name("command")
+                      .help("Command to run and its arguments")
+                      .value_name("COMMAND")
+                      .multiple(true)
+                      .required(true)
+                      .last(true)))
+            .group(ArgGroup::with_name("read").args(&["list", "query"]))
+            .group(ArgGroup::with_name("select").args(&["list", "extend"]))
+            .group(ArgGroup::with_name("modify").args(&["remove", "extend"]))
             .get_matches())
     }
 
-    pub fn getconf(&self) -> ShushConfig {
+    /// Load config file from `-f` or the default locations
+    pub fn getconf(&self) -> Result<ShushConfig, SensuError> {
         ShushConfig::new(self.get_match("configfile"))
     }
 
-    pub fn getopts(&self) -> ShushOpts {
+    /// Convert arguments into the action to take, interpreting times in the time zone from
+    /// the `timezone` config option or the local time zone
+    pub fn getopts(&self, cfg: &ShushConfig) -> Result<ShushOpts, SensuError> {
+        let tz = cfg.get_timezone()?;
+        let schedule = || {
+            let begin = self.get_match("begin");
+            let until = self.get_match("until");
+            match tz {
+                Some(tz) => get_schedule(begin, until, &Utc::now().with_timezone(&tz)),
+                None => get_schedule(begin, until, &Local::now()),
+            }
+        };
+
         let clearopts = |matches: &ArgMatches, res_arg, res_type| {
-            ShushOpts::Clear(ClearOpts {
+            Ok(ShushOpts::Clear(ClearOpts {
                 resources: matches.value_of(res_arg).map(|st| ShushResources {
                     resources: st.split(",").map(|s| s.to_string()).collect(),
                     res_type,
                 }),
                 checks: matches.value_of("checks")
                     .map(|st| st.split(",").map(|s| s.to_string()).collect()),
-            })
+                ids: None,
+            }))
+        };
+
+        let extension = || {
+            let (_, until) = schedule()?;
+            if let Some(u) = until {
+                return Ok(Extension::Until(u));
+            }
+            match (self.get_match("expire"), self.get_match("extendby")) {
+                (Some(ref e), _) if e == "none" => Ok(Extension::Never),
+                (Some(e), _) => Ok(Extension::For(get_silence_duration(&e)?)),
+                (None, Some(a)) => Ok(Extension::Add(time::parse_duration(&a)?)),
+                (None, None) => Err(SensuError::new(
+                    "--extend requires a new expiration with --expire, --until or --add"
+                )),
+            }
+        };
+
+        // Extend mode selects silences with the same filters as list mode, except that it only
+        // extends the subscriptions and checks named and not every one containing them
+        let exact = |names: Option<String>| names.map(|n| {
+            n.split(',').map(regex::escape).collect::<Vec<_>>().join("|")
+        });
+        let selection = |l: ListOpts| if self.0.is_present("extend") {
+            let l = ListOpts { sub: exact(l.sub), chk: exact(l.chk), ..l };
+            Ok(ShushOpts::Extend(ExtendOpts { selection: l, ids: None, extension: extension()? }))
+        } else {
+            Ok(ShushOpts::List(l))
         };
 
         let listopts = |matches: &ArgMatches, res_arg| {
-            ShushOpts::List(ListOpts {
+            selection(ListOpts {
+                clients: None,
                 sub: matches.value_of(res_arg).map(|st| st.to_string()),
                 chk: matches.value_of("checks").map(|st| st.to_string()),
+                filter: self.get_list_filter()?,
+                sort: self.get_list_sort(),
+                output: self.get_output()?,
             })
         };
 
-        let expiration = |matches: &ArgMatches| {
+        let clientlistopts = |matches: &ArgMatches, res_arg, res_type| {
+            selection(ListOpts {
+                clients: matches.value_of(res_arg).map(|st| ShushResources {
+                    resources: st.split(",").map(|s| s.to_string()).collect(),
+                    res_type,
+                }),
+                sub: None,
+                chk: matches.value_of("checks").map(|st| st.to_string()),
+                filter: self.get_list_filter()?,
+                sort: self.get_list_sort(),
+                output: self.get_output()?,
+            })
+        };
+
+        let expiration = |matches: &ArgMatches, until: Option<i64>| {
+            let eor = matches.is_present("expireonresolve");
+            if let Some(u) = until {
+                return Ok(Expire::Until(u, eor));
+            }
             get_expiration(matches.value_of("expire").map(|s| s.to_string())
-                           .unwrap_or("2h".to_string()),
-                           matches.is_present("expireonresolve"))
+                           .unwrap_or("2h".to_string()), eor)
         };
 
         let silenceopts = |matches: &ArgMatches, res_arg, res_type| {
-            ShushOpts::Silence(SilenceOpts {
+            let (begin, until) = schedule()?;
+            Ok(ShushOpts::Silence(SilenceOpts {
                 resources: matches.value_of(res_arg).map(|st| ShushResources {
                     resources: st.split(",").map(|s| s.to_string()).collect(),
                     res_type,
                 }),
                 checks: matches.value_of("checks").map(|st| st.split(",")
                                                        .map(|s| s.to_string()).collect()),
-                expire: expiration(matches),
-            })
+                expire: expiration(matches, until)?,
+                reason: matches.value_of("reason").map(|r| r.to_string()),
+                begin,
+            }))
         };
 
         let matches = &self.0;
-        let shush_opts = if matches.is_present("nodes") {
+        if let Some(m) = matches.subcommand_matches("exec") {
+            return self.get_exec(m, cfg).map(ShushOpts::Exec);
+        }
+        if let Some(path) = matches.value_of("apply").or_else(|| matches.value_of("diff")) {
+            let manifest = Manifest::load(path)?;
+            let silences = match tz {
+                Some(tz) => manifest.into_silences(&Utc::now().with_timezone(&tz)),
+                None => manifest.into_silences(&Local::now()),
+            }?;
+            Ok(ShushOpts::Apply(ApplyOpts { silences }))
+        } else if let Some(path) = matches.value_of("export") {
+            Ok(ShushOpts::Export(path.to_string()))
+        } else if let Some(path) = matches.value_of("import") {
+            Ok(ShushOpts::Import(ImportOpts { export: Export::load(path)? }))
+        } else if matches.is_present("undo") {
+            match matches.value_of("undo") {
+                Some(id) => id.parse().map(|id| ShushOpts::Undo(Some(id))).map_err(|_| {
+                    SensuError::new_string(format_args!(
+                        "--undo takes the number of an invocation from --history, found {}", id
+                    ))
+                }),
+                None => Ok(ShushOpts::Undo(None)),
+            }
+        } else if matches.is_present("history") {
+            Ok(ShushOpts::History)
+        } else if matches.is_present("query") {
+            let client = if matches.is_present("nodes") {
+                ShushResources {
+                    resources: self.get_match_as_vec("nodes").unwrap_or_default(),
+                    res_type: ShushResourceType::Node,
+                }
+            } else if matches.is_present("ids") {
+                ShushResources {
+                    resources: self.get_match_as_vec("ids").unwrap_or_default(),
+                    res_type: ShushResourceType::Client,
+                }
+            } else if let Some(client) = get_client_target(matches, cfg)? {
+                client
+            } else {
+                return Err(SensuError::new("--query requires a client with -i, an instance ID \
+                                            with -n, --self, --by or --where"));
+            };
+            Ok(ShushOpts::Query(QueryOpts {
+                client,
+                checks: self.get_match_as_vec("checks").unwrap_or_default(),
+                output: self.get_output()?,
+            }))
+        } else if matches.is_present("silenceids") && matches.is_present("extend") {
+            Ok(ShushOpts::Extend(ExtendOpts {
+                selection: ListOpts::default(),
+                ids: self.get_match_as_vec("silenceids"),
+                extension: extension()?,
+            }))
+        } else if matches.is_present("silenceids") {
+            Ok(ShushOpts::Clear(ClearOpts {
+                resources: None,
+                checks: None,
+                ids: self.get_match_as_vec("silenceids"),
+            }))
+        } else if let Some(target) = get_client_target(matches, cfg)? {
+            let resources = Some(target);
+            if matches.is_present("remove") {
+                Ok(ShushOpts::Clear(ClearOpts {
+                    resources,
+                    checks: self.get_match_as_vec("checks"),
+                    ids: None,
+                }))
+            } else if matches.is_present("select") {
+                selection(ListOpts {
+                    clients: resources,
+                    sub: None,
+                    chk: self.get_match("checks"),
+                    filter: self.get_list_filter()?,
+                    sort: self.get_list_sort(),
+                    output: self.get_output()?,
+                })
+            } else {
+                let (begin, until) = schedule()?;
+                Ok(ShushOpts::Silence(SilenceOpts {
+                    resources,
+                    checks: self.get_match_as_vec("checks"),
+                    expire: expiration(matches, until)?,
+                    reason: self.get_match("reason"),
+                    begin,
+                }))
+            }
+        } else if matches.is_present("nodes") {
             if matches.is_present("remove") {
                 clearopts(matches, "nodes", ShushResourceType::Node)
-            } else if matches.is_present("list") {
-                listopts(matches, "nodes")
+            } else if matches.is_present("select") {
+                clientlistopts(matches, "nodes", ShushResourceType::Node)
             } else {
                 silenceopts(matches, "nodes", ShushResourceType::Node)
             }
         } else if matches.is_present("ids") {
             if matches.is_present("remove") {
                 clearopts(matches, "ids", ShushResourceType::Client)
-            } else if matches.is_present("list") {
-                listopts(matches, "ids")
+            } else if matches.is_present("select") {
+                clientlistopts(matches, "ids", ShushResourceType::Client)
             } else {
                 silenceopts(matches, "ids", ShushResourceType::Client)
             }
         } else if matches.is_present("subscriptions") {
             if matches.is_present("remove") {
                 clearopts(matches, "subscriptions", ShushResourceType::Sub)
-            } else if matches.is_present("list") {
+            } else if matches.is_present("select") {
                 listopts(matches, "subscriptions")
             } else {
                 silenceopts(matches, "subscriptions", ShushResourceType::Sub)
             }
         } else {
             if matches.is_present("remove") {
-                ShushOpts::Clear(ClearOpts {
+                Ok(ShushOpts::Clear(ClearOpts {
                     resources: None,
                     checks: self.get_match_as_vec("checks"),
-                })
-            } else if matches.is_present("list") {
-                ShushOpts::List(ListOpts {
+                    ids: None,
+                }))
+            } else if matches.is_present("select") {
+                selection(ListOpts {
+                    clients: None,
                     sub: None,
                     chk: self.get_match("checks"),
+                    filter: self.get_list_filter()?,
+                    sort: self.get_list_sort(),
+                    output: self.get_output()?,
                 })
             } else {
-                ShushOpts::Silence(SilenceOpts {
+                let (begin, until) = schedule()?;
+                Ok(ShushOpts::Silence(SilenceOpts {
                     resources: None,
                     checks: self.get_match_as_vec("checks"),
-                    expire: expiration(matches),
-                })
+                    expire: expiration(matches, until)?,
+                    reason: self.get_match("reason"),
+                    begin,
+                }))
             }
+        }
+    }
+
+    fn get_exec(&self, matches: &ArgMatches, cfg: &ShushConfig) -> Result<ExecOpts, SensuError> {
+        let ttl = get_silence_duration(matches.value_of("ttl").unwrap_or("10m"))?;
+        let target = if matches.is_present("nodes") {
+            Some(("nodes", ShushResourceType::Node))
+        } else if matches.is_present("ids") {
+            Some(("ids", ShushResourceType::Client))
+        } else if matches.is_present("subscriptions") {
+            Some(("subscriptions", ShushResourceType::Sub))
+        } else {
+            None
+        };
+        let resources = match target {
+            Some((arg, res_type)) => Some(ShushResources {
+                resources: matches.value_of(arg).unwrap_or_default().split(",")
+                    .map(|s| s.to_string()).collect(),
+                res_type,
+            }),
+            None => get_client_target(matches, cfg)?,
+        };
+        Ok(ExecOpts {
+            silence: SilenceOpts {
+                resources,
+                checks: matches.value_of("checks")
+                    .map(|st| st.split(",").map(|s| s.to_string()).collect()),
+                expire: Expire::Expire(ttl, false),
+                reason: matches.value_of("reason").map(|r| r.to_string()),
+                begin: None,
+            },
+            renew_every: (ttl / 3).max(1),
+            command: matches.values_of("command").map(|v| v.map(|s| s.to_string()).collect())
+                .unwrap_or_default(),
+        })
+    }
+
+    /// Whether silence, clear, extend, apply, import and undo actions should only be previewed
+    pub fn dry_run(&self) -> bool {
+        self.0.is_present("dryrun") || self.0.is_present("diff")
+    }
+
+    fn get_list_filter(&self) -> Result<ListFilter, SensuError> {
+        let creator = if self.0.is_present("mine") {
+            Some(format!("^{}$", regex::escape(&sensu::current_user())))
+        } else {
+            self.get_match("creator")
         };
-        shush_opts
+        let expires_within = match self.get_match("expireswithin") {
+            Some(d) => Some(time::parse_duration(&d)?),
+            None => None,
+        };
+        Ok(ListFilter {
+            creator,
+            never_expires: self.0.is_present("neverexpires"),
+            expires_within,
+            target: match self.0.value_of("target") {
+                Some("client") => Some(TargetType::Client),
+                Some(_) => Some(TargetType::Subscription),
+                None => None,
+            },
+        })
     }
 
+    fn get_list_sort(&self) -> Option<ListSort> {
+        match self.0.value_of("sort") {
+            Some("creator") => Some(ListSort::Creator),
+            Some(_) => Some(ListSort::Expiry),
+            None => None,
+        }
+    }
+
+    fn get_output(&self) -> Result<OutputFormat, SensuError> {
+        match self.0.value_of("output") {
+            Some(o) => o.parse(),
+            None => Ok(OutputFormat::default()),
+        }
+    }
+
+    /// Get value of an option
     pub fn get_match(&self, option: &str) -> Option<String> {
         self.0.value_of(option).map(|s| s.to_string())
     }
 
+    /// Get comma separated value of an option as a list
     pub fn get_match_as_vec(&self, option: &str) -> Option<Vec<String>> {
         self.0.value_of(option).map(|st| st.split(",").map(|s| s.to_string()).collect())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_get_begin() {
+        let now = Local.timestamp_opt(1500000000, 0).unwrap();
+        assert_eq!(get_begin("in 3h", &now).unwrap(), 1500000000 + 3 * 60 * 60);
+        assert_eq!(get_begin("@1600000000", &now).unwrap(), 1600000000);
+        assert!(get_begin("1600000000", &now).is_err());
+        assert_eq!(get_begin("2017-07-14T02:40:00Z", &now).unwrap(), 1500000000);
+        assert!(get_begin("2017-07-14 02:40", &now).is_ok());
+        assert!(get_begin("next tuesday", &now).is_err());
+        assert!(get_begin("in 9223372036854774784", &now).is_err());
+    }
+
+    #[test]
+    fn test_get_until() {
+        let now = Utc.timestamp_opt(1500000000, 0).unwrap();
+        assert_eq!(get_until("17:30", None, &now).unwrap(), 1500053400);
+        assert!(get_until("2017-07-13 17:30", None, &now).is_err());
+        assert!(get_until("17:30", Some(1500060000), &now).is_err());
+    }
+
+    #[test]
+    fn test_get_lookup() {
+        let aliases = Some("gcp = gcp.instance_name, host=system.hostname".to_string());
+        assert_eq!(get_lookup("gcp=vm-1,vm-2", aliases.clone()).unwrap(), ShushResources {
+            res_type: ShushResourceType::Attribute("/gcp/instance_name".to_string()),
+            resources: vec!["vm-1".to_string(), "vm-2".to_string()],
+        });
+        assert_eq!(get_lookup("ec2.instance_id=i-123", aliases).unwrap().res_type,
+                   ShushResourceType::Attribute("/ec2/instance_id".to_string()));
+        assert_eq!(get_lookup("/metadata/labels/region=us-east-1", None).unwrap().res_type,
+                   ShushResourceType::Attribute("/metadata/labels/region".to_string()));
+        assert!(get_lookup("address", None).is_err());
+        assert!(get_lookup("address=", None).is_err());
+        assert!(get_lookup("=10.0.0.1", None).is_err());
+    }
+
+    #[test]
+    fn test_get_expiration() {
+        assert_eq!(get_expiration("1h30m".to_string(), true).unwrap(), Expire::Expire(5400, true));
+        assert_eq!(get_expiration("none".to_string(), false).unwrap(),
+                   Expire::NoExpiration(false));
+        assert_eq!(get_expiration("2 hours".to_string(), false).unwrap(), Expire::Expire(7200, false));
+        assert!(get_expiration("2 horas".to_string(), false).is_err());
+        assert!(get_expiration("0".to_string(), false).is_err());
+        assert!(get_expiration("0h 0m".to_string(), false).is_err());
+    }
+}
@@ -0,0 +1,389 @@
+//! Rendering of silence listings in human and machine readable formats
+
+use std::io::Write;
+use std::str::FromStr;
+use std::time::{SystemTime,UNIX_EPOCH};
+
+use chrono::{Local,TimeZone};
+use chrono_tz::Tz;
+use csv;
+use serde_json;
+use serde_yaml;
+
+use actions::Coverage;
+use err::SensuError;
+use journal::Invocation;
+use sensu::Silence;
+use time::format_duration;
+
+/// Format to render silences in
+#[derive(Clone,Copy,Debug,Default,PartialEq)]
+pub enum OutputFormat {
+    /// Indented block per silence
+    #[default]
+    Text,
+    /// JSON array
+    Json,
+    /// One JSON object per line
+    JsonLines,
+    /// YAML sequence
+    Yaml,
+    /// CSV with a header row
+    Csv,
+    /// Aligned table with a header row
+    Table,
+}
+
+/// Names accepted by `OutputFormat::from_str`
+pub const OUTPUT_FORMATS: &[&str] = &["text", "json", "jsonl", "yaml", "csv", "table"];
+
+impl FromStr for OutputFormat {
+    type Err = SensuError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(OutputFormat::Text),
+            "json" => Ok(OutputFormat::Json),
+            "jsonl" => Ok(OutputFormat::JsonLines),
+            "yaml" => Ok(OutputFormat::Yaml),
+            "csv" => Ok(OutputFormat::Csv),
+            "table" => Ok(OutputFormat::Table),
+            _ => Err(SensuError::new_string(format_args!(
+                "Unknown output format {} - expected one of {}", s, OUTPUT_FORMATS.join(", ")
+            ))),
+        }
+    }
+}
+
+/// Header row of CSV output - the fields of `Silence` in order, written even if there are no
+/// silences so that an empty result can be told from a broken one
+const CSV_HEADER: &[&str] = &["id", "subscription", "check", "creator", "reason", "expire",
+                              "expire_on_resolve", "begin", "timestamp", "expire_at"];
+
+/// Format a UNIX timestamp in the given time zone, or local time if `None`
+pub fn timestamp(ts: i64, tz: Option<Tz>) -> String {
+    match tz {
+        Some(tz) => tz.timestamp_opt(ts, 0).single()
+            .map(|t| t.format("%Y-%m-%d %H:%M:%S %Z").to_string()),
+        None => Local.timestamp_opt(ts, 0).single()
+            .map(|t| t.format("%Y-%m-%d %H:%M:%S %z").to_string()),
+    }.unwrap_or_else(|| ts.to_string())
+}
+
+/// Current time and the time zone to render times relative to
+#[derive(Clone,Copy,Debug)]
+struct Clock {
+    now: i64,
+    tz: Option<Tz>,
+}
+
+impl Clock {
+    fn new(tz: Option<Tz>) -> Self {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64)
+            .unwrap_or(0);
+        Clock { now, tz }
+    }
+
+    fn timestamp(&self, ts: i64) -> String {
+        timestamp(ts, self.tz)
+    }
+
+    /// Remaining time of a silence such as `1h 12m left`
+    fn remaining(&self, s: &Silence) -> String {
+        match s.expires_at(self.now) {
+            Some(at) => format!("{} left", format_duration((at - self.now).max(0) as u64)),
+            None => "never".to_string(),
+        }
+    }
+
+    /// Remaining time of a silence along with the time it expires at
+    fn expiration(&self, s: &Silence) -> String {
+        match s.expires_at(self.now) {
+            Some(at) => format!("{} ({})", self.remaining(s), self.timestamp(at)),
+            None => "never".to_string(),
+        }
+    }
+}
+
+/// Split silences into those already in effect and those with a begin time in the future
+fn partition(silences: &[Silence], now: i64) -> (Vec<&Silence>, Vec<&Silence>) {
+    silences.iter().partition(|s| s.begin.map(|b| b <= now).unwrap_or(true))
+}
+
+/// Footer counting the silences in a listing
+fn summary(silences: &[Silence], clock: &Clock) -> String {
+    let (active, pending) = partition(silences, clock.now);
+    let never = silences.iter().filter(|s| s.expires_at(clock.now).is_none()).count();
+    format!("{} silence{} - {} active, {} pending, {} without expiration", silences.len(),
+            if silences.len() == 1 { "" } else { "s" }, active.len(), pending.len(), never)
+}
+
+fn text_section(lines: &mut Vec<String>, title: &str, silences: &[&Silence], clock: &Clock) {
+    lines.push(title.to_string());
+    if silences.is_empty() {
+        lines.push("\tNo silences".to_string());
+    }
+    for s in silences {
+        lines.push(format!("\tID:\t\t\t{}", s.id));
+        lines.push(format!("\tSubscription:\t\t{}", s.subscription.as_deref().unwrap_or("all")));
+        lines.push(format!("\tCheck:\t\t\t{}", s.check.as_deref().unwrap_or("all")));
+        lines.push(format!("\tExpiration:\t\t{}", clock.expiration(s)));
+        lines.push(format!("\tExpire on resolve:\t{}", s.expire_on_resolve));
+        lines.push(format!("\tUser:\t\t\t{}", s.creator.as_deref().unwrap_or("unknown")));
+        if let Some(ref reason) = s.reason {
+            lines.push(format!("\tReason:\t\t\t{}", reason));
+        }
+        if let Some(begin) = s.begin {
+            lines.push(format!("\tBegin:\t\t\t{}", clock.timestamp(begin)));
+        }
+        if let Some(timestamp) = s.timestamp {
+            lines.push(format!("\tCreated:\t\t{}", clock.timestamp(timestamp)));
+        }
+        lines.push(String::new());
+    }
+}
+
+fn write_text<W>(w: &mut W, silences: &[Silence], clock: &Clock) -> Result<(), SensuError>
+        where W: Write {
+    let (active, pending) = partition(silences, clock.now);
+    let mut lines = Vec::new();
+    text_section(&mut lines, "Active silences:", &active, clock);
+    if !pending.is_empty() {
+        text_section(&mut lines, "Pending silences:", &pending, clock);
+    }
+    lines.push(summary(silences, clock));
+    lines.iter().try_for_each(|l| writeln!(w, "{}", l)).map_err(SensuError::new_string)
+}
+
+/// Write rows as columns aligned under a header row
+fn write_aligned<W>(w: &mut W, header: &[&str], rows: &[Vec<String>]) -> Result<(), SensuError>
+        where W: Write {
+    let widths: Vec<usize> = header.iter().enumerate().map(|(i, h)| {
+        rows.iter().map(|r| r[i].chars().count()).chain(Some(h.len())).max().unwrap_or(0)
+    }).collect();
+
+    let header_row: Vec<String> = header.iter().map(|h| h.to_string()).collect();
+    for row in Some(&header_row).into_iter().chain(rows.iter()) {
+        let cells: Vec<String> = row.iter().zip(widths.iter())
+            .map(|(cell, width)| format!("{:width$}", cell, width = width)).collect();
+        writeln!(w, "{}", cells.join("  ").trim_end()).map_err(SensuError::new_string)?;
+    }
+    Ok(())
+}
+
+fn table_section<W>(w: &mut W, silences: &[&Silence], pending: bool, clock: &Clock)
+        -> Result<(), SensuError> where W: Write {
+    let mut header = vec!["ID", "SUBSCRIPTION", "CHECK", "EXPIRATION", "EXPIRES AT",
+                          "EXPIRE ON RESOLVE", "USER", "CREATED", "REASON"];
+    if pending {
+        header.insert(0, "BEGIN");
+    }
+    let rows: Vec<Vec<String>> = silences.iter().map(|s| {
+        let mut row = vec![
+            s.id.clone(),
+            s.subscription.clone().unwrap_or_else(|| "all".to_string()),
+            s.check.clone().unwrap_or_else(|| "all".to_string()),
+            clock.remaining(s),
+            s.expires_at(clock.now).map(|at| clock.timestamp(at)).unwrap_or_default(),
+            s.expire_on_resolve.to_string(),
+            s.creator.clone().unwrap_or_else(|| "unknown".to_string()),
+            s.timestamp.map(|t| clock.timestamp(t)).unwrap_or_default(),
+            s.reason.clone().unwrap_or_default(),
+        ];
+        if pending {
+            row.insert(0, s.begin.map(|b| clock.timestamp(b)).unwrap_or_default());
+        }
+        row
+    }).collect();
+    write_aligned(w, &header, &rows)
+}
+
+fn write_table<W>(w: &mut W, silences: &[Silence], clock: &Clock) -> Result<(), SensuError>
+        where W: Write {
+    let (active, pending) = partition(silences, clock.now);
+    table_section(w, &active, false, clock)?;
+    if !pending.is_empty() {
+        writeln!(w, "\nPending silences:").map_err(SensuError::new_string)?;
+        table_section(w, &pending, true, clock)?;
+    }
+    writeln!(w, "\n{}", summary(silences, clock)).map_err(SensuError::new_string)
+}
+
+fn write_silences_at<W>(w: &mut W, silences: &[Silence], format: OutputFormat, clock: &Clock)
+        -> Result<(), SensuError> where W: Write {
+    match format {
+        OutputFormat::Text => write_text(w, silences, clock),
+        OutputFormat::Table => write_table(w, silences, clock),
+        OutputFormat::Json => {
+            serde_json::to_writer_pretty(&mut *w, silences).map_err(SensuError::new_string)?;
+            writeln!(w).map_err(SensuError::new_string)
+        },
+        OutputFormat::JsonLines => silences.iter().try_for_each(|s| {
+            serde_json::to_writer(&mut *w, s).map_err(SensuError::new_string)?;
+            writeln!(w).map_err(SensuError::new_string)
+        }),
+        OutputFormat::Yaml => {
+            serde_yaml::to_writer(&mut *w, silences).map_err(SensuError::new_string)?;
+            writeln!(w).map_err(SensuError::new_string)
+        },
+        OutputFormat::Csv => {
+            let mut writer = csv::WriterBuilder::new().has_headers(false).from_writer(w);
+            writer.write_record(CSV_HEADER).map_err(SensuError::new_string)?;
+            for s in silences {
+                writer.serialize(s).map_err(SensuError::new_string)?;
+            }
+            writer.flush().map_err(SensuError::new_string)
+        },
+    }
+}
+
+/// Write silences to `w` in the given format, rendering times in the time zone `tz` or local
+/// time if `None`
+pub fn write_silences<W>(w: &mut W, silences: &[Silence], format: OutputFormat, tz: Option<Tz>)
+        -> Result<(), SensuError> where W: Write {
+    write_silences_at(w, silences, format, &Clock::new(tz))
+}
+
+/// Write the result of a silence query to `w` in the given format - tabular formats only
+/// contain the matching silences
+pub fn write_coverage<W>(w: &mut W, coverage: &[Coverage], format: OutputFormat,
+                         tz: Option<Tz>) -> Result<(), SensuError> where W: Write {
+    let clock = Clock::new(tz);
+    match format {
+        OutputFormat::Text => coverage.iter().try_for_each(|c| {
+            if !c.silenced {
+                return writeln!(w, "{} on {} is not silenced", c.check, c.client)
+                    .map_err(SensuError::new_string);
+            }
+            writeln!(w, "{} on {} is silenced by:", c.check, c.client)
+                .map_err(SensuError::new_string)?;
+            c.silences.iter().try_for_each(|s| {
+                let expiry = match s.expires_at(clock.now) {
+                    Some(_) => clock.expiration(s),
+                    None => "does not expire".to_string(),
+                };
+                let resolve = if s.expire_on_resolve { ", or until the check resolves" } else { "" };
+                writeln!(w, "\t{} - {}{}", s.id, expiry, resolve)
+            }).map_err(SensuError::new_string)
+        }),
+        OutputFormat::Json => {
+            serde_json::to_writer_pretty(&mut *w, coverage).map_err(SensuError::new_string)?;
+            writeln!(w).map_err(SensuError::new_string)
+        },
+        OutputFormat::JsonLines => coverage.iter().try_for_each(|c| {
+            serde_json::to_writer(&mut *w, c).map_err(SensuError::new_string)?;
+            writeln!(w).map_err(SensuError::new_string)
+        }),
+        OutputFormat::Yaml => {
+            serde_yaml::to_writer(&mut *w, coverage).map_err(SensuError::new_string)?;
+            writeln!(w).map_err(SensuError::new_string)
+        },
+        OutputFormat::Csv | OutputFormat::Table => {
+            let silences: Vec<Silence> = coverage.iter().flat_map(|c| c.silences.clone()).collect();
+            write_silences_at(w, &silences, format, &clock)
+        },
+    }
+}
+
+/// Write the invocations in the journal as a table, rendering times in the time zone `tz` or
+/// local time if `None`
+pub fn write_history<W>(w: &mut W, invocations: &[Invocation], tz: Option<Tz>)
+        -> Result<(), SensuError> where W: Write {
+    if invocations.is_empty() {
+        return writeln!(w, "No invocations recorded").map_err(SensuError::new_string);
+    }
+    let rows: Vec<Vec<String>> = invocations.iter().map(|i| vec![
+        i.id.to_string(),
+        timestamp(i.timestamp, tz),
+        i.silences.len().to_string(),
+        i.undone.map(|u| timestamp(u, tz)).unwrap_or_default(),
+        i.command.join(" "),
+    ]).collect();
+    write_aligned(w, &["ID", "CREATED", "SILENCES", "UNDONE", "COMMAND"], &rows)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn silences() -> Vec<Silence> {
+        serde_json::from_str(r#"[
+            {"id": "client:host1:*", "subscription": "client:host1", "expire": 3600,
+             "creator": "jbaublitz", "reason": "kernel upgrade", "timestamp": 1499997600},
+            {"id": "*:check_disk", "check": "check_disk", "expire": -1,
+             "expire_on_resolve": true, "creator": "ops"}
+        ]"#).unwrap()
+    }
+
+    fn clock(now: i64) -> Clock {
+        Clock { now, tz: Some(Tz::UTC) }
+    }
+
+    fn render(format: OutputFormat) -> String {
+        let mut out = Vec::new();
+        write_silences_at(&mut out, &silences(), format, &clock(1500000000)).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_table() {
+        assert_eq!(render(OutputFormat::Table), "\
+ID              SUBSCRIPTION  CHECK       EXPIRATION  EXPIRES AT               EXPIRE ON RESOLVE  USER       CREATED                  REASON
+client:host1:*  client:host1  all         1h left     2017-07-14 03:40:00 UTC  false              jbaublitz  2017-07-14 02:00:00 UTC  kernel upgrade
+*:check_disk    all           check_disk  never                                true               ops
+
+2 silences - 2 active, 0 pending, 1 without expiration
+");
+    }
+
+    #[test]
+    fn test_text_times() {
+        let text = render(OutputFormat::Text);
+        assert!(text.contains("\tExpiration:\t\t1h left (2017-07-14 03:40:00 UTC)\n"), "{}", text);
+        assert!(text.contains("\tExpiration:\t\tnever\n"), "{}", text);
+        assert!(text.contains("\tCreated:\t\t2017-07-14 02:00:00 UTC\n"), "{}", text);
+    }
+
+    #[test]
+    fn test_pending_section() {
+        let mut silences = silences();
+        silences[1].begin = Some(2000);
+        let mut out = Vec::new();
+        write_text(&mut out, &silences, &clock(1000)).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        let pending = text.find("Pending silences:").unwrap();
+        assert!(text.find("client:host1:*").unwrap() < pending);
+        assert!(text.find("*:check_disk").unwrap() > pending);
+
+        let mut out = Vec::new();
+        write_text(&mut out, &silences, &clock(3000)).unwrap();
+        assert!(!String::from_utf8(out).unwrap().contains("Pending silences:"));
+    }
+
+    #[test]
+    fn test_csv() {
+        let csv = render(OutputFormat::Csv);
+        let mut lines = csv.lines();
+        assert_eq!(lines.next(), Some("id,subscription,check,creator,reason,expire,\
+                                       expire_on_resolve,begin,timestamp,expire_at"));
+        assert_eq!(lines.next(), Some("client:host1:*,client:host1,,jbaublitz,kernel upgrade,\
+                                       3600,false,,1499997600,"));
+
+        let mut out = Vec::new();
+        write_silences_at(&mut out, &[], OutputFormat::Csv, &clock(0)).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), format!("{}\n", CSV_HEADER.join(",")));
+    }
+
+    #[test]
+    fn test_json_lines() {
+        let jsonl = render(OutputFormat::JsonLines);
+        let parsed: Vec<Silence> = jsonl.lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+        assert_eq!(parsed, silences());
+    }
+
+    #[test]
+    fn test_parse_format() {
+        assert_eq!("yaml".parse::<OutputFormat>().unwrap(), OutputFormat::Yaml);
+        assert!("xml".parse::<OutputFormat>().is_err());
+    }
+}
@@ -1,6 +1,10 @@
+//! Shush target resources as passed on the command line
+
 use std::fmt::{self,Display};
 use std::vec;
 
+use sensu::ClientInfo;
+
 /// Enum representing Shush target resource type (AWS node, Sensu client, or subscription)
 #[derive(PartialEq,Debug)]
 pub enum ShushResourceType {
@@ -10,12 +14,22 @@ pub enum ShushResourceType {
     Client,
     /// Sensu subscription
     Sub,
+    /// Clients whose attribute at the JSON pointer, such as `/ec2/instance_id`, has one of the
+    /// given values
+    Attribute(String),
+    /// Clients matching a selector expression (see `selector::Selector`)
+    Selector,
+    /// Clients already resolved from other targets, such as a selector that was previewed,
+    /// which are acted on as they are without looking them up again
+    Resolved(Vec<ClientInfo>),
 }
 
 /// List of resources and the resource type
 #[derive(PartialEq,Debug)]
 pub struct ShushResources {
+    /// Type of all resources in the list
     pub res_type: ShushResourceType,
+    /// Resource names or IDs
     pub resources: Vec<String>,
 }
 
@@ -32,10 +46,14 @@ impl Display for ShushResources {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self.res_type {
             ShushResourceType::Node => write!(f, "Instance IDs: ")?,
-            ShushResourceType::Client => write!(f, "Sensu clients: ")?,
+            ShushResourceType::Client | ShushResourceType::Resolved(_) => {
+                write!(f, "Sensu clients: ")?
+            },
             ShushResourceType::Sub => write!(f, "Subscriptions: ")?,
+            ShushResourceType::Attribute(ref p) => write!(f, "Clients with {}: ", p)?,
+            ShushResourceType::Selector => write!(f, "Clients matching: ")?,
         };
-        write!(f, "{}", if self.resources.len() > 0 {
+        write!(f, "{}", if !self.resources.is_empty() {
             self.resources.join(", ")
         } else {
             return Err(fmt::Error);
@@ -43,6 +61,7 @@ impl Display for ShushResources {
     }
 }
 
+/// Iterator over the resource names in `ShushResources`
 pub struct ShushResourceIterator(vec::IntoIter<String>);
 
 impl Iterator for ShushResourceIterator {
@@ -0,0 +1,286 @@
+//! Client selectors such as `environment=staging and role in (db, cache)`, evaluated against
+//! the attributes of every client
+//!
+//! A selector compares client attributes, given as dotted paths like for `--by`, with `=`,
+//! `!=`, `=~` and `!~` for regex matches, or `in` and `not in` followed by a parenthesized
+//! list of values. Comparisons are combined with `and`, `or`, `not` and parentheses, where
+//! `and` binds tighter than `or`. Values containing whitespace, commas or parentheses are
+//! quoted with `"` or `'`. Array attributes such as `subscriptions` match if any of their
+//! elements does.
+
+use nom::IResult;
+use nom::branch::alt;
+use nom::bytes::complete::{is_not,tag,tag_no_case,take_while1};
+use nom::character::complete::{char,multispace0,multispace1};
+use nom::combinator::{all_consuming,cut,map,not,opt,value};
+use nom::error::{ErrorKind,ParseError};
+use nom::multi::{fold_many0,separated_nonempty_list};
+use nom::sequence::{delimited,preceded,terminated,tuple};
+use regex::{Regex,RegexBuilder};
+
+use backend::{missing_target,Validated};
+use err::SensuError;
+use opts::attribute_pointer;
+use resources::ShushResourceType;
+use sensu::ClientInfo;
+
+/// Expression selecting clients by their attributes
+#[derive(Clone,Debug)]
+pub enum Selector {
+    /// Attribute at the JSON pointer equals the value
+    Eq(String, String),
+    /// Attribute at the JSON pointer matches the regex
+    Match(String, Regex),
+    /// Attribute at the JSON pointer equals one of the values
+    In(String, Vec<String>),
+    /// Inner selector does not match
+    Not(Box<Selector>),
+    /// Both selectors match
+    And(Box<Selector>, Box<Selector>),
+    /// Either selector matches
+    Or(Box<Selector>, Box<Selector>),
+}
+
+/// Position of a syntax error, or the error of an invalid regex
+#[derive(Debug)]
+struct SyntaxError<'a> {
+    rest: &'a str,
+    regex: Option<String>,
+}
+
+impl<'a> ParseError<&'a str> for SyntaxError<'a> {
+    fn from_error_kind(rest: &'a str, _: ErrorKind) -> Self {
+        SyntaxError { rest, regex: None }
+    }
+
+    fn append(_: &'a str, _: ErrorKind, other: Self) -> Self {
+        other
+    }
+}
+
+type Parsed<'a, O> = IResult<&'a str, O, SyntaxError<'a>>;
+
+#[derive(Clone,Copy)]
+enum Op {
+    Eq,
+    Ne,
+    Match,
+    NotMatch,
+    In,
+    NotIn,
+}
+
+fn ws<'a, O, F>(inner: F) -> impl Fn(&'a str) -> Parsed<'a, O>
+        where F: Fn(&'a str) -> Parsed<'a, O> {
+    delimited(multispace0, inner, multispace0)
+}
+
+fn is_path_char(c: char) -> bool {
+    c.is_alphanumeric() || "_-./".contains(c)
+}
+
+/// Keyword that is neither the start of a longer path nor compared itself
+fn keyword<'a>(word: &'static str) -> impl Fn(&'a str) -> Parsed<'a, &'a str> {
+    terminated(tag_no_case(word),
+               not(take_while1(|c: char| is_path_char(c) || "=!~".contains(c))))
+}
+
+fn path(i: &str) -> Parsed<'_, &str> {
+    take_while1(is_path_char)(i)
+}
+
+fn literal(i: &str) -> Parsed<'_, String> {
+    map(alt((
+        delimited(char('"'), map(opt(is_not("\"")), |v| v.unwrap_or_default()), char('"')),
+        delimited(char('\''), map(opt(is_not("'")), |v| v.unwrap_or_default()), char('\'')),
+        take_while1(|c: char| !c.is_whitespace() && !"()\"',".contains(c)),
+    )), |v: &str| v.to_string())(i)
+}
+
+fn list(i: &str) -> Parsed<'_, Vec<String>> {
+    delimited(ws(char('(')), separated_nonempty_list(char(','), ws(literal)), char(')'))(i)
+}
+
+fn comparison(i: &str) -> Parsed<'_, Selector> {
+    let (i, path) = ws(path)(i)?;
+    let pointer = attribute_pointer(path);
+    let (i, op) = ws(alt((
+        value(Op::Eq, tag("==")),
+        value(Op::Match, tag("=~")),
+        value(Op::Eq, tag("=")),
+        value(Op::Ne, tag("!=")),
+        value(Op::NotMatch, tag("!~")),
+        value(Op::NotIn, tuple((keyword("not"), multispace1, keyword("in")))),
+        value(Op::In, keyword("in")),
+    )))(i)?;
+    let negate = |s| Selector::Not(Box::new(s));
+    match op {
+        Op::In | Op::NotIn => {
+            let (i, values) = list(i)?;
+            let selector = Selector::In(pointer, values);
+            Ok((i, if let Op::NotIn = op { negate(selector) } else { selector }))
+        },
+        Op::Match | Op::NotMatch => {
+            let (rest, pattern) = literal(i)?;
+            let regex = RegexBuilder::new(&pattern).size_limit(8192).dfa_size_limit(8192)
+                .build()
+                .map_err(|e| nom::Err::Failure(SyntaxError {
+                    rest: i,
+                    regex: Some(e.to_string()),
+                }))?;
+            let selector = Selector::Match(pointer, regex);
+            Ok((rest, if let Op::NotMatch = op { negate(selector) } else { selector }))
+        },
+        Op::Eq | Op::Ne => {
+            let (i, v) = literal(i)?;
+            let selector = Selector::Eq(pointer, v);
+            Ok((i, if let Op::Ne = op { negate(selector) } else { selector }))
+        },
+    }
+}
+
+fn unary(i: &str) -> Parsed<'_, Selector> {
+    ws(alt((
+        map(preceded(alt((keyword("not"), tag("!"))), cut(unary)),
+            |s| Selector::Not(Box::new(s))),
+        delimited(char('('), or_expr, char(')')),
+        comparison,
+    )))(i)
+}
+
+fn and_expr(i: &str) -> Parsed<'_, Selector> {
+    let (i, first) = unary(i)?;
+    fold_many0(preceded(keyword("and"), cut(unary)), first,
+               |acc, s| Selector::And(Box::new(acc), Box::new(s)))(i)
+}
+
+fn or_expr(i: &str) -> Parsed<'_, Selector> {
+    let (i, first) = and_expr(i)?;
+    fold_many0(preceded(keyword("or"), cut(and_expr)), first,
+               |acc, s| Selector::Or(Box::new(acc), Box::new(s)))(i)
+}
+
+impl Selector {
+    /// Parse a selector expression
+    pub fn parse(expression: &str) -> Result<Self, SensuError> {
+        match all_consuming(or_expr)(expression) {
+            Ok((_, selector)) => Ok(selector),
+            Err(nom::Err::Error(e)) | Err(nom::Err::Failure(e)) => {
+                Err(match e.regex {
+                    Some(r) => SensuError::new_string(format_args!(
+                        "Invalid regex in selector \"{}\": {}", expression, r
+                    )),
+                    None if e.rest.trim().is_empty() => SensuError::new_string(format_args!(
+                        "Selector \"{}\" ends unexpectedly", expression
+                    )),
+                    None => SensuError::new_string(format_args!(
+                        "Failed to parse selector \"{}\" at \"{}\"", expression, e.rest.trim()
+                    )),
+                })
+            },
+            Err(nom::Err::Incomplete(_)) => Err(SensuError::new_string(format_args!(
+                "Selector \"{}\" ends unexpectedly", expression
+            ))),
+        }
+    }
+
+    /// Whether the client's attributes satisfy the selector
+    pub fn matches(&self, client: &ClientInfo) -> bool {
+        match *self {
+            Selector::Eq(ref p, ref v) => client.attribute_strings(p).iter().any(|a| a == v),
+            Selector::Match(ref p, ref r) => client.attribute_strings(p).iter()
+                .any(|a| r.is_match(a)),
+            Selector::In(ref p, ref vs) => client.attribute_strings(p).iter()
+                .any(|a| vs.contains(a)),
+            Selector::Not(ref s) => !s.matches(client),
+            Selector::And(ref a, ref b) => a.matches(client) && b.matches(client),
+            Selector::Or(ref a, ref b) => a.matches(client) || b.matches(client),
+        }
+    }
+}
+
+/// Clients matching any of the selector expressions, with a warning for every expression that
+/// matches none of them
+pub fn select(expressions: Vec<String>, clients: Vec<ClientInfo>)
+              -> Result<Validated<ClientInfo>, SensuError> {
+    let selectors = expressions.iter().map(|e| Selector::parse(e))
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut selected = Validated::default();
+    for (expression, selector) in expressions.iter().zip(selectors.iter()) {
+        if !clients.iter().any(|c| selector.matches(c)) {
+            selected.warnings.push(missing_target(&ShushResourceType::Selector, expression));
+        }
+    }
+    selected.items = clients.into_iter().filter(|c| selectors.iter().any(|s| s.matches(c)))
+        .collect();
+    Ok(selected)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json;
+
+    fn clients() -> Vec<ClientInfo> {
+        serde_json::from_str(r#"[
+            {"name": "db1", "subscriptions": ["linux", "db"], "environment": "staging",
+             "role": "db", "ec2": {"region": "us-east-1"}},
+            {"name": "db2", "subscriptions": ["linux", "db"], "environment": "prod",
+             "role": "db", "ec2": {"region": "eu-west-1"}},
+            {"name": "web1", "subscriptions": ["linux", "web"], "environment": "staging",
+             "role": "web"},
+            {"name": "cache1", "subscriptions": ["linux"], "environment": "dev", "role": "cache"}
+        ]"#).unwrap()
+    }
+
+    fn selected(expression: &str) -> Vec<String> {
+        let selector = Selector::parse(expression).unwrap();
+        clients().into_iter().filter(|c| selector.matches(c)).map(|c| c.name).collect()
+    }
+
+    #[test]
+    fn test_matches() {
+        assert_eq!(selected("environment=staging and role=db"), vec!["db1"]);
+        assert_eq!(selected("environment = staging or role == db"), vec!["db1", "db2", "web1"]);
+        assert_eq!(selected("role in (db, 'cache') and not environment=prod"),
+                   vec!["db1", "cache1"]);
+        assert_eq!(selected("ec2.region =~ ^us- or name !~ \"[0-9]$\""), vec!["db1"]);
+        assert_eq!(selected("subscriptions=web"), vec!["web1"]);
+        assert_eq!(selected("role not in (db,web)"), vec!["cache1"]);
+        assert_eq!(selected("environment != staging AND (role=db OR role=cache)"),
+                   vec!["db2", "cache1"]);
+        assert_eq!(selected("!(ec2.region=us-east-1)and role=db"), vec!["db2"]);
+        // Missing attributes never equal anything
+        assert_eq!(selected("ec2.region != us-east-1"), vec!["db2", "web1", "cache1"]);
+        // Keywords only count as such when followed by whitespace or parentheses
+        assert_eq!(selected("role=db or order=1"), vec!["db1", "db2"]);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let error = |e: &str| Selector::parse(e).unwrap_err().to_string();
+        assert_eq!(error("role=db and"), "Selector \"role=db and\" ends unexpectedly");
+        assert!(error("role=db extra").contains("at \"extra\""));
+        assert!(error("role =~ '(unclosed'").starts_with("Invalid regex in selector"));
+        assert!(Selector::parse("role in ()").is_err());
+        assert!(Selector::parse("(role=db").is_err());
+        assert!(Selector::parse("").is_err());
+    }
+
+    #[test]
+    fn test_oversized_regex() {
+        let error = Selector::parse("role =~ '\\w{1000}{1000}'").unwrap_err().to_string();
+        assert!(error.starts_with("Invalid regex in selector"));
+        assert!(error.contains("size limit"));
+    }
+
+    #[test]
+    fn test_select() {
+        let selected = select(vec!["role=cache".to_string(), "role=nosuch".to_string()],
+                              clients()).unwrap();
+        assert_eq!(selected.items.len(), 1);
+        assert_eq!(selected.warnings,
+                   vec!["Selector \"role=nosuch\" matches no clients - filtering...".to_string()]);
+        assert!(select(vec!["role=".to_string()], clients()).is_err());
+    }
+}
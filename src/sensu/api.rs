@@ -0,0 +1,80 @@
+use err::SensuError;
+use resources::ShushResourceType;
+
+/// Enum representing the flavor of the Sensu API that shush is talking to
+#[derive(Clone,Debug,PartialEq)]
+pub enum SensuApi {
+    /// Sensu 0.29/1.x REST API
+    Legacy,
+    /// Sensu Go (2.x+) API scoped to the contained namespace
+    Go(String),
+}
+
+impl SensuApi {
+    /// Build API flavor from the `backend` and `namespace` config options
+    pub fn new(backend: Option<String>, namespace: Option<String>) -> Result<Self, SensuError> {
+        match backend.as_deref() {
+            None | Some("legacy") | Some("1.x") => Ok(SensuApi::Legacy),
+            Some("go") | Some("2.x") => {
+                Ok(SensuApi::Go(namespace.unwrap_or_else(|| "default".to_string())))
+            },
+            Some(b) => Err(SensuError::new_string(format_args!(
+                r#"Unrecognized backend "{}" - expected "legacy" or "go""#, b
+            ))),
+        }
+    }
+
+    /// Subscription prefix Sensu uses for the implicit per-client subscription
+    pub fn client_prefix(&self) -> &'static str {
+        match *self {
+            SensuApi::Legacy => "client:",
+            SensuApi::Go(_) => "entity:",
+        }
+    }
+
+    /// JSON pointer to the AWS instance ID in the attributes of a client or entity
+    pub fn instance_id_pointer(&self) -> &'static str {
+        match *self {
+            SensuApi::Legacy => "/instance_id",
+            SensuApi::Go(_) => "/metadata/labels/instance_id",
+        }
+    }
+
+    /// JSON pointer to the attribute clients are looked up by for a target type - `None` for
+    /// targets that name clients or subscriptions directly
+    pub fn lookup_pointer<'a>(&self, res_type: &'a ShushResourceType) -> Option<&'a str> {
+        match *res_type {
+            ShushResourceType::Node => Some(self.instance_id_pointer()),
+            ShushResourceType::Attribute(ref p) => Some(p),
+            ShushResourceType::Client | ShushResourceType::Sub |
+            ShushResourceType::Selector | ShushResourceType::Resolved(_) => None,
+        }
+    }
+}
+
+/// Name Sensu Go assigns a silence entry for a subscription and check pair
+pub fn silenced_name(sub: Option<&str>, chk: Option<&str>) -> String {
+    format!("{}:{}", sub.unwrap_or("*"), chk.unwrap_or("*"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_api_from_config() {
+        assert_eq!(SensuApi::new(None, None).unwrap(), SensuApi::Legacy);
+        assert_eq!(SensuApi::new(Some("go".to_string()), None).unwrap(),
+                   SensuApi::Go("default".to_string()));
+        assert_eq!(SensuApi::new(Some("go".to_string()), Some("prod".to_string())).unwrap(),
+                   SensuApi::Go("prod".to_string()));
+        assert!(SensuApi::new(Some("nagios".to_string()), None).is_err());
+    }
+
+    #[test]
+    fn test_silenced_name() {
+        assert_eq!(silenced_name(Some("entity:host1"), None), "entity:host1:*");
+        assert_eq!(silenced_name(None, Some("check_disk")), "*:check_disk");
+        assert_eq!(silenced_name(Some("linux"), Some("check_disk")), "linux:check_disk");
+    }
+}
@@ -0,0 +1,117 @@
+use std::time::{SystemTime,UNIX_EPOCH};
+
+use base64;
+use serde_json::Value;
+
+use super::SensuApi;
+use err::SensuError;
+
+/// Credentials used to authenticate against the Sensu API
+#[derive(Clone,Debug,PartialEq)]
+pub enum SensuAuth {
+    /// No `Authorization` header
+    Anonymous,
+    /// HTTP basic authentication with user and password (Sensu 1.x)
+    Basic(String, String),
+    /// Sensu Go API key
+    ApiKey(String),
+    /// Sensu Go access token obtained from `/auth` with user and password
+    Token(String, String),
+}
+
+impl SensuAuth {
+    /// Pick authentication method from the configured credentials and API flavor
+    pub fn new(api: &SensuApi, user: Option<String>, password: Option<String>,
+               api_key: Option<String>) -> Result<Self, SensuError> {
+        match (api, user, password, api_key) {
+            (&SensuApi::Legacy, _, _, Some(_)) => {
+                Err(SensuError::new("API keys are only supported by Sensu Go"))
+            },
+            (_, _, _, Some(key)) => Ok(SensuAuth::ApiKey(key)),
+            (&SensuApi::Legacy, Some(u), Some(p), None) => Ok(SensuAuth::Basic(u, p)),
+            (&SensuApi::Go(_), Some(u), Some(p), None) => Ok(SensuAuth::Token(u, p)),
+            (_, Some(_), None, None) | (_, None, Some(_), None) => {
+                Err(SensuError::new("user and password must be configured together"))
+            },
+            (_, None, None, None) => Ok(SensuAuth::Anonymous),
+        }
+    }
+}
+
+/// Value of a basic `Authorization` header
+pub fn basic_auth_header(user: &str, password: &str) -> String {
+    format!("Basic {}", base64::encode(&format!("{}:{}", user, password)))
+}
+
+/// Access token returned by the Sensu Go `/auth` endpoints
+#[derive(Clone,Debug,PartialEq)]
+pub struct AccessToken {
+    /// Bearer token for API requests
+    pub access_token: String,
+    /// Token used to get a new access token from `/auth/token`
+    pub refresh_token: String,
+    /// UNIX timestamp when the access token expires
+    pub expires_at: u64,
+}
+
+impl AccessToken {
+    /// Parse token from the body of an `/auth` or `/auth/token` response
+    pub fn from_value(value: Option<Value>) -> Result<Self, SensuError> {
+        let field = |v: &Value, name| v.get(name).and_then(|f| f.as_str()).map(|s| s.to_string());
+        match value {
+            Some(ref v) => match (field(v, "access_token"), field(v, "refresh_token"),
+                                  v.get("expires_at").and_then(|e| e.as_u64())) {
+                (Some(access_token), Some(refresh_token), Some(expires_at)) => Ok(AccessToken {
+                    access_token,
+                    refresh_token,
+                    expires_at,
+                }),
+                _ => Err(SensuError::new("Malformed access token in authentication response")),
+            },
+            None => Err(SensuError::new("Empty authentication response")),
+        }
+    }
+
+    /// Whether the token expires within the next 10 seconds
+    pub fn is_expired(&self) -> bool {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        self.expires_at <= now + 10
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json;
+
+    #[test]
+    fn test_auth_from_config() {
+        let go = SensuApi::Go("default".to_string());
+        let s = |st: &str| Some(st.to_string());
+        assert_eq!(SensuAuth::new(&SensuApi::Legacy, s("admin"), s("secret"), None).unwrap(),
+                   SensuAuth::Basic("admin".to_string(), "secret".to_string()));
+        assert_eq!(SensuAuth::new(&go, s("admin"), s("secret"), None).unwrap(),
+                   SensuAuth::Token("admin".to_string(), "secret".to_string()));
+        assert_eq!(SensuAuth::new(&go, s("admin"), s("secret"), s("key")).unwrap(),
+                   SensuAuth::ApiKey("key".to_string()));
+        assert_eq!(SensuAuth::new(&go, None, None, None).unwrap(), SensuAuth::Anonymous);
+        assert!(SensuAuth::new(&SensuApi::Legacy, None, None, s("key")).is_err());
+        assert!(SensuAuth::new(&go, s("admin"), None, None).is_err());
+    }
+
+    #[test]
+    fn test_basic_auth_header() {
+        assert_eq!(basic_auth_header("admin", "P@ssw0rd!"), "Basic YWRtaW46UEBzc3cwcmQh");
+    }
+
+    #[test]
+    fn test_access_token() {
+        let token = AccessToken::from_value(serde_json::from_str(
+            r#"{"access_token": "abc", "refresh_token": "def", "expires_at": 1000}"#
+        ).unwrap()).unwrap();
+        assert_eq!(token.access_token, "abc");
+        assert!(token.is_expired());
+        assert!(AccessToken::from_value(serde_json::from_str(r#"{"access_token": "abc"}"#)
+                .unwrap()).is_err());
+    }
+}
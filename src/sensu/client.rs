@@ -1,185 +1,208 @@
 use std::borrow::Borrow;
 use std::collections::{HashMap,HashSet};
-use std::convert::TryInto;
 use std::error::Error;
-use std::fmt::Display;
-use std::process;
 
 use serde_json::{self,Value,Map};
 use hyper::{Body,Client,Method,Request,StatusCode,Uri};
 use hyper::client::HttpConnector;
+use hyper_tls::HttpsConnector;
 use hyper::header::{self,HeaderValue};
 use hyper::rt::{Future,Stream};
-use regex::{Regex,RegexBuilder};
 use tokio::runtime::Runtime;
 
 use super::*;
+use backend::{missing_target,PlannedRequest,SilenceBackend,Validated};
+use config::ShushConfig;
 use err::SensuError;
-use opts::{ClearOpts,ListOpts,SilenceOpts};
 use resources::{ShushResources,ShushResourceType};
+use selector;
 
-pub struct SensuClient(Client<HttpConnector>, Runtime, Uri);
+/// HTTP client for the Sensu API
+pub struct SensuClient {
+    client: Client<HttpsConnector<HttpConnector>>,
+    runtime: Runtime,
+    base_uri: Uri,
+    api: SensuApi,
+    auth: SensuAuth,
+    token: Option<AccessToken>,
+}
 
 impl SensuClient {
-    pub fn new(base_url: String) -> Result<Self, Box<dyn Error>> {
-        Ok(SensuClient(Client::builder().build(HttpConnector::new(4)), Runtime::new()?,
-                       base_url.parse::<Uri>()?))
+    /// Build client from the `api`, backend, TLS and credential options in the shush config
+    pub fn from_config(cfg: &ShushConfig) -> Result<Self, Box<dyn Error>> {
+        let api = SensuApi::new(cfg.get("backend"), cfg.get("namespace"))?;
+        let tls = TlsConfig {
+            ca_bundle: cfg.get("ca_bundle"),
+            client_cert: cfg.get("client_cert"),
+            client_key: cfg.get("client_key"),
+            insecure_skip_verify: cfg.get_bool("insecure_skip_verify")?,
+        };
+        let auth = SensuAuth::new(&api, cfg.get("user"), cfg.get_secret("password")?,
+                                  cfg.get_secret("api_key")?)?;
+        SensuClient::new(cfg.get("api").unwrap_or_default(), api, &tls, auth)
+    }
+
+    /// Build client for the API at `base_url`
+    pub fn new(base_url: String, api: SensuApi, tls: &TlsConfig, auth: SensuAuth)
+            -> Result<Self, Box<dyn Error>> {
+        Ok(SensuClient {
+            client: Client::builder().build(tls.connector()?),
+            runtime: Runtime::new()?,
+            base_uri: base_url.parse::<Uri>()?,
+            api,
+            auth,
+            token: None,
+        })
+    }
+
+    /// Send request to a Sensu endpoint and parse the JSON response, if any
+    pub fn request(&mut self, method: Method, endpoint: SensuEndpoint, body: Option<Map<String, Value>>)
+            -> Result<Option<Value>, SensuError> {
+        let uri = self.uri(endpoint)?;
+        let authorization = self.authorization()?;
+        self.send(method, uri, body, authorization)
     }
 
-    pub fn request<U>(&mut self, method: Method, uri: U, body: Option<SensuPayload>)
-            -> Result<Option<Value>, SensuError> where U: TryInto<Uri>, U::Error: Display {
-        let mut full_uri = uri.try_into().map_err(|e| SensuError::new_string(e))?;
-        let map: Option<Map<String, Value>> = body.map(|b| b.into());
+    /// Resolve an endpoint to a full URI against the configured base URL
+    fn uri(&self, endpoint: SensuEndpoint) -> Result<Uri, SensuError> {
+        let mut full_uri = endpoint.uri(&self.api).map_err(SensuError::Message)?;
         if full_uri.authority_part().is_none() {
             let mut parts = full_uri.into_parts();
-            let base_uri = self.2.clone().into_parts();
+            let base_uri = self.base_uri.clone().into_parts();
             parts.scheme = base_uri.scheme;
             parts.authority = base_uri.authority;
-            full_uri = Uri::from_parts(parts).map_err(|e| SensuError::new(e.description()))?;
+            full_uri = Uri::from_parts(parts).map_err(SensuError::new_string)?;
         }
+        Ok(full_uri)
+    }
+
+    fn execute(&mut self, plan: PlannedRequest) -> Result<(), SensuError> {
+        let authorization = self.authorization()?;
+        self.send(plan.method, plan.uri, plan.body, authorization).map(|_| ())
+    }
 
+    /// Value of the `Authorization` header for the configured credentials, logging in or
+    /// refreshing the access token first if needed
+    fn authorization(&mut self) -> Result<Option<String>, SensuError> {
+        let (user, password) = match self.auth {
+            SensuAuth::Anonymous => return Ok(None),
+            SensuAuth::Basic(ref u, ref p) => return Ok(Some(basic_auth_header(u, p))),
+            SensuAuth::ApiKey(ref k) => return Ok(Some(format!("Key {}", k))),
+            SensuAuth::Token(ref u, ref p) => (u.clone(), p.clone()),
+        };
+
+        let token = match self.token.take() {
+            Some(t) => if t.is_expired() {
+                let bearer = format!("Bearer {}", t.access_token);
+                let mut body = Map::new();
+                body.insert("refresh_token".to_string(), Value::String(t.refresh_token));
+                let refreshed = self.uri(SensuEndpoint::AuthToken).and_then(|uri| {
+                    self.send(Method::POST, uri, Some(body), Some(bearer))
+                });
+                match refreshed.and_then(AccessToken::from_value) {
+                    Ok(t) => t,
+                    Err(_) => AccessToken::from_value(self.send(
+                        Method::GET, self.uri(SensuEndpoint::Auth)?, None,
+                        Some(basic_auth_header(&user, &password))
+                    )?)?,
+                }
+            } else {
+                t
+            },
+            None => AccessToken::from_value(self.send(Method::GET, self.uri(SensuEndpoint::Auth)?,
+                                                      None,
+                                                      Some(basic_auth_header(&user, &password)))?)?,
+        };
+        let header = format!("Bearer {}", token.access_token);
+        self.token = Some(token);
+        Ok(Some(header))
+    }
+
+    fn send(&mut self, method: Method, full_uri: Uri, body: Option<Map<String, Value>>,
+            authorization: Option<String>) -> Result<Option<Value>, SensuError> {
         let mut builder = Request::builder();
         builder.method(method).uri(full_uri);
-        let req = if let Some(ref m) = map {
-            let body_string = serde_json::to_string(m).map_err(|e| {
-                SensuError::new(e.description())
-            })?;
+        if let Some(auth) = authorization {
+            builder.header(header::AUTHORIZATION, auth);
+        }
+        let req = if let Some(ref m) = body {
+            let body_string = serde_json::to_string(m).map_err(SensuError::new_string)?;
             builder.header(header::CONTENT_LENGTH, body_string.len())
             .header(header::CONTENT_TYPE, HeaderValue::from_static("application/json"))
             .body(Body::from(body_string))
-            .map_err(|e| SensuError::new(e.description()))?
+            .map_err(SensuError::new_string)?
         } else {
-            builder.body(Body::empty()).map_err(|e| SensuError::new(e.description()))?
+            builder.body(Body::empty()).map_err(SensuError::new_string)?
         };
 
-        self.1.block_on(self.0.request(req).map_err(|e| {
-            SensuError::from(e)
-        }).and_then(|resp| {
+        self.runtime.block_on(self.client.request(req).map_err(SensuError::from).and_then(|resp| {
             if resp.status() == StatusCode::NOT_FOUND {
                 return Err(SensuError::not_found());
             }
+            if resp.status() == StatusCode::UNAUTHORIZED || resp.status() == StatusCode::FORBIDDEN {
+                return Err(SensuError::new_string(format_args!(
+                    "{} - check the credentials in the shush config file", resp.status()
+                )));
+            }
             Ok(resp)
         }).and_then(|resp| {
-            resp.into_body().concat2().map_err(|e| SensuError::from(e))
+            resp.into_body().concat2().map_err(SensuError::from)
         }).and_then(|chunk| {
-            if chunk.len() < 1 {
+            if chunk.is_empty() {
                 return Ok(None);
             }
             serde_json::from_slice::<Value>(&chunk).map_err(|e| {
-                println!("Response: {}", match std::str::from_utf8(&chunk).map_err(|e| {
-                    SensuError::new(&e.to_string())
-                }) {
-                    Ok(j) => j,
-                    Err(e) => return e,
-                });
-                SensuError::new(&e.to_string())
+                SensuError::new_string(format_args!("{}\nResponse: {}", e,
+                                                    String::from_utf8_lossy(&chunk)))
             }).map(Some)
         }))
     }
 
-    fn get_node_to_client_map(&mut self) -> Result<HashMap<String, String>, Box<dyn Error>> {
-        let clients = self.request(Method::GET, SensuEndpoint::Clients, None)?;
-
-        let mut client_map = HashMap::new();
-        if let Some(Value::Array(v)) = clients {
-            for mut item in v {
-                let iid = item.as_object_mut().and_then(|obj| obj.remove("instance_id"));
-                let iid_string = match iid {
-                    Some(Value::String(string)) => Some(string),
-                    _ => None,
-                };
-
-                let client = item.as_object_mut().and_then(|client| client.remove("name"));
-                let client_string = match client {
-                    Some(Value::String(string)) => Some(string),
-                    _ => None,
-                };
-
-                if let (Some(i), Some(c)) = (iid_string, client_string) {
-                    client_map.insert(i, c);
-                }
-            }
-        }
+    /// Fetch all clients, or entities on Sensu Go
+    pub fn clients(&mut self) -> Result<Vec<ClientInfo>, SensuError> {
+        let resp = self.request(Method::GET, SensuEndpoint::Clients, None)?;
+        self.api.parse_clients(resp)
+    }
 
-        Ok(client_map)
+    /// Fetch a single client, or entity on Sensu Go
+    pub fn client(&mut self, name: &str) -> Result<ClientInfo, SensuError> {
+        let resp = self.request(Method::GET, SensuEndpoint::Client(name), None)?;
+        self.api.parse_client(resp)
     }
 
-    fn map_to_sensu_resources(&mut self, res: ShushResources)
-            -> Result<Vec<SensuResource>, Box<dyn Error>>{
-        let (resource_type, resources) = (res.res_type, res.resources);
-        let mut map = self.get_node_to_client_map()?;
-        let mapped_resources = match resource_type {
-            ShushResourceType::Node => resources.iter().filter_map(|v| {
-                if let Some(val) = map.remove(v) {
-                    if self.validate_client(val.as_str()) {
-                        Some(SensuResource::Client(val))
-                    } else {
-                        None
-                    }
-                } else {
-                    println!(r#"WARNING: instance ID "{}" not associated with Sensu client ID"#, v);
-                    println!("If you recently provisioned an instance, please wait for it to \
-                             register with Sensu");
-                    println!();
-                    None
-                }
-            }).collect(),
-            ShushResourceType::Sub => {
-                let subs = resources.into_iter().map(SensuResource::Subscription).collect();
-                self.validate_subscriptions(subs)
-            },
-            ShushResourceType::Client => resources.into_iter()
-                .filter_map(|c| {
-                    if self.validate_client(c.as_str()) {
-                        Some(SensuResource::Client(c))
-                    } else {
-                        None
-                    }
-                }).collect(),
-        };
-        Ok(mapped_resources)
+    /// Fetch latest check results, or events on Sensu Go
+    pub fn results(&mut self) -> Result<Vec<CheckResult>, SensuError> {
+        let resp = self.request(Method::GET, SensuEndpoint::Results, None)?;
+        self.api.parse_results(resp)
     }
 
-    fn validate_client(&mut self, client_name: &str) -> bool {
-        let resp = self.request(Method::GET, SensuEndpoint::Client(client_name), None);
-        if let Err(SensuError::NotFound) = resp {
-            return false;
-        } else {
-            return true;
+    /// Map every value of the attribute at `pointer` to the clients that have it
+    fn get_attribute_to_clients_map(&mut self, pointer: &str)
+            -> Result<HashMap<String, Vec<ClientInfo>>, SensuError> {
+        let mut map: HashMap<String, Vec<ClientInfo>> = HashMap::new();
+        for c in self.clients()? {
+            for value in c.attribute_strings(pointer) {
+                map.entry(value).or_default().push(c.clone());
+            }
         }
+        Ok(map)
     }
 
-    fn validate_subscriptions(&mut self, subscriptions: Vec<SensuResource>) -> Vec<SensuResource> {
-        let print_error = || {
-            println!("Failed to pull data from API for subscriptions");
-            println!("Proceeding without subscription validation");
-        };
+    fn validate_subscriptions(&mut self, subscriptions: Vec<SensuResource>,
+                              warnings: &mut Vec<String>) -> Vec<SensuResource> {
+        let skipped = "Failed to pull data from API for subscriptions\n\
+                       Proceeding without subscription validation".to_string();
 
-        let resp_res = self.request(Method::GET, SensuEndpoint::Clients, None);
-        let resp = match resp_res {
+        let subs: HashSet<String> = match self.clients() {
             Err(SensuError::NotFound) => {
-                print_error();
+                warnings.push(skipped);
                 return subscriptions;
             },
             Err(SensuError::Message(s)) => {
-                println!("{}", s);
+                warnings.push(s);
                 return subscriptions;
             },
-            Ok(resp) => resp,
-        };
-
-        let subs: HashSet<String> = if let Some(Value::Array(vec)) = resp {
-            vec.into_iter().filter_map(|obj| {
-                obj.as_object().and_then(|o| o.get("subscriptions"))
-                    .and_then(|subs| subs.as_array()).map(|arr| {
-                        let v: Vec<String> = arr.into_iter()
-                            .filter_map(|s| s.as_str().map(|st| st.to_string())).collect();
-                        v
-                    })
-            }).flatten().collect()
-        } else {
-            print_error();
-            return subscriptions;
+            Ok(clients) => clients.into_iter().flat_map(|c| c.subscriptions).collect(),
         };
 
         subscriptions.into_iter().filter(|sub| {
@@ -187,195 +210,165 @@ impl SensuClient {
             if subs.contains(string) {
                 true
             } else {
-                println!("Subscription {} does not exist - filtering...", sub);
+                warnings.push(missing_target(&ShushResourceType::Sub, string));
                 false
             }
         }).collect()
     }
 
-    fn validate_checks(&mut self, checks: Vec<String>) -> Vec<String> {
-        let print_error = || {
-            println!("Failed to pull data from API for check results");
-            println!("Proceeding without check validation");
-        };
+    fn filter_checks(&mut self, checks: Vec<String>, warnings: &mut Vec<String>) -> Vec<String> {
+        let skipped = "Failed to pull data from API for check results\n\
+                       Proceeding without check validation".to_string();
 
-        let results_res = self.request(Method::GET, SensuEndpoint::Results, None);
-        let results = match results_res {
+        let hs: HashSet<String> = match self.results() {
             Err(SensuError::NotFound) => {
-                print_error();
+                warnings.push(skipped);
                 return checks;
             },
             Err(SensuError::Message(s)) => {
-                println!("{}", s);
+                warnings.push(s);
                 return checks;
             },
-            Ok(res) => res,
+            Ok(results) => results.into_iter().map(|r| r.check.name).collect(),
         };
 
-        let hs: HashSet<String> = if let Some(Value::Array(vec)) = results {
-             vec.into_iter().filter_map(|obj| {
-                obj.as_object().and_then(|o| o.get("check"))
-                    .and_then(|c| c.get("name"))
-                    .and_then(|n| n.as_str().map(|st| st.to_string()))
-            }).collect()
-        } else {
-            print_error();
-            return checks;
-        };
-
-        let filtered_checks: Vec<String> = checks.into_iter().filter(|chk| {
+        checks.into_iter().filter(|chk| {
             if hs.contains(chk) {
                 true
             } else {
-                println!("Check {} does not exist - filtering...", chk);
+                warnings.push(format!("Check {} does not exist - filtering...", chk));
                 false
             }
-        }).collect();
+        }).collect()
+    }
+}
 
-        filtered_checks
+impl SilenceBackend for SensuClient {
+    fn resolve_targets(&mut self, res: ShushResources) -> Result<Validated<String>, SensuError> {
+        if res.res_type != ShushResourceType::Sub {
+            let clients = self.resolve_clients(res)?;
+            let prefix = self.api.client_prefix();
+            return Ok(Validated {
+                items: clients.items.into_iter().map(|c| format!("{}{}", prefix, c.name)).collect(),
+                warnings: clients.warnings,
+            });
+        }
+        let mut warnings = Vec::new();
+        let subs = res.resources.into_iter().map(SensuResource::Subscription).collect();
+        let items = self.validate_subscriptions(subs, &mut warnings).into_iter()
+            .map(|r| r.subscription(&self.api)).collect();
+        Ok(Validated { items, warnings })
     }
 
-    pub fn silence(&mut self, s: SilenceOpts) -> Result<(), Box<dyn Error>> {
-        let resources: Option<Vec<String>> = match s.resources {
-            Some(res) => Some(self.map_to_sensu_resources(res)?.into_iter()
-                .map(|r| format!("{}", r)).collect()),
-            None => None,
-        };
-        let checks = s.checks.map(|cks| self.validate_checks(cks));
-        let expire = s.expire;
-        match (resources, checks) {
-            (Some(res), Some(chk)) => iproduct!(res, chk).for_each(|(r, c)| {
-                println!("Silencing check {} on resource {} and will {}", c, r, expire);
-                let _ = self.request(Method::POST, SensuEndpoint::Silenced, Some(SensuPayload {
-                    res: Some(r),
-                    chk: Some(c),
-                    expire: Some(expire.clone()),
-                })).map_err(|e| {
-                    println!("{}", e);
-                    process::exit(1);
-                });
-            }),
-            (Some(res), None) => res.into_iter().for_each(|r| {
-                println!("Silencing all checks on resource {} and will {}", r, expire);
-                let _ = self.request(Method::POST, SensuEndpoint::Silenced, Some(SensuPayload {
-                    res: Some(r),
-                    chk: None,
-                    expire: Some(expire.clone()),
-                })).map_err(|e| {
-                    println!("{}", e);
-                    process::exit(1);
-                });
-            }),
-            (None, Some(chk)) => chk.into_iter().for_each(|c| {
-                println!("Silencing checks {} on all resources and will {}", c, expire);
-                let _ = self.request(Method::POST, SensuEndpoint::Silenced, Some(SensuPayload {
-                    res: None,
-                    chk: Some(c),
-                    expire: Some(expire.clone()),
-                })).map_err(|e| {
-                    println!("{}", e);
-                    process::exit(1);
-                });
-            }),
-            (_, _) => {
-                println!("No targets specified - Exiting...");
-                process::exit(1);
+    fn resolve_clients(&mut self, res: ShushResources)
+                       -> Result<Validated<ClientInfo>, SensuError> {
+        let mut resolved = Validated::default();
+        match res.res_type {
+            ShushResourceType::Node | ShushResourceType::Attribute(_) => {
+                let res_type = &res.res_type;
+                let pointer = self.api.lookup_pointer(res_type).unwrap_or_default();
+                let mut map = self.get_attribute_to_clients_map(pointer)?;
+                for v in res.resources {
+                    let found = map.remove(&v).unwrap_or_default();
+                    if found.is_empty() {
+                        resolved.warnings.push(missing_target(res_type, &v));
+                    }
+                    resolved.items.extend(found);
+                }
             },
-        };
-        Ok(())
+            ShushResourceType::Client => {
+                for name in res.resources {
+                    match self.client(&name) {
+                        Ok(c) => resolved.items.push(c),
+                        Err(SensuError::NotFound) => resolved.warnings.push(
+                            missing_target(&ShushResourceType::Client, &name)
+                        ),
+                        Err(e) => return Err(e),
+                    }
+                }
+            },
+            ShushResourceType::Sub => {
+                let clients = self.clients()?;
+                for sub in res.resources {
+                    let found: Vec<ClientInfo> = clients.iter()
+                        .filter(|c| c.subscriptions.contains(&sub)).cloned().collect();
+                    if found.is_empty() {
+                        resolved.warnings.push(missing_target(&ShushResourceType::Sub, &sub));
+                    }
+                    resolved.items.extend(found);
+                }
+            },
+            ShushResourceType::Selector => {
+                let selected = selector::select(res.resources, self.clients()?)?;
+                resolved.warnings.extend(selected.warnings);
+                resolved.items.extend(selected.items);
+            },
+            ShushResourceType::Resolved(clients) => resolved.items.extend(clients),
+        }
+        // A client matching several targets is only resolved once
+        let mut seen = HashSet::new();
+        resolved.items.retain(|c| seen.insert(c.name.clone()));
+        for c in resolved.items.iter_mut() {
+            let client_sub = format!("{}{}", self.api.client_prefix(), c.name);
+            if !c.subscriptions.contains(&client_sub) {
+                c.subscriptions.push(client_sub);
+            }
+        }
+        Ok(resolved)
     }
 
-    pub fn clear(&mut self, s: ClearOpts) -> Result<(), Box<dyn Error>> {
-        let resources: Option<Vec<String>> = match s.resources {
-            Some(res) => Some(self.map_to_sensu_resources(res)?.into_iter()
-                .map(|r| format!("{}", r)).collect()),
-            None => None,
-        };
-        let checks = s.checks;
-        match (resources, checks) {
-            (Some(res), Some(chk)) => iproduct!(res, chk).for_each(|(r, c)| {
-                println!("Clearing silences on checks {} on resources {}", c, r);
-                let _ = self.request(Method::POST, SensuEndpoint::Clear, Some(SensuPayload {
-                    res: Some(r),
-                    chk: Some(c),
-                    expire: None,
-                })).map_err(|e| {
-                    println!("{}", e);
-                    process::exit(1);
-                });
-            }),
-            (Some(res), None) => res.into_iter().for_each(|r| {
-                println!("Clearing silences on all checks on resources {}", r);
-                let _ = self.request(Method::POST, SensuEndpoint::Clear, Some(SensuPayload {
-                    res: Some(r),
-                    chk: None,
-                    expire: None,
-                })).map_err(|e| {
-                    println!("{}", e);
-                    process::exit(1);
-                });
-            }),
-            (None, Some(chk)) => chk.into_iter().for_each(|c| {
-                println!("Clearing silences on checks {} on all resources", c);
-                let _ = self.request(Method::POST, SensuEndpoint::Clear, Some(SensuPayload {
-                    res: None,
-                    chk: Some(c),
-                    expire: None,
-                })).map_err(|e| {
-                    println!("{}", e);
-                    process::exit(1);
-                });
+    fn validate_checks(&mut self, checks: Vec<String>) -> Validated<String> {
+        let mut warnings = Vec::new();
+        let items = self.filter_checks(checks, &mut warnings);
+        Validated { items, warnings }
+    }
+
+    fn client_prefix(&self) -> &'static str {
+        self.api.client_prefix()
+    }
+
+    fn clients(&mut self) -> Result<Vec<ClientInfo>, SensuError> {
+        SensuClient::clients(self)
+    }
+
+    fn plan_create(&self, payload: &SensuPayload) -> Result<PlannedRequest, SensuError> {
+        Ok(PlannedRequest {
+            method: Method::POST,
+            uri: self.uri(SensuEndpoint::Silenced)?,
+            body: Some(payload.clone().into_body(&self.api)?),
+        })
+    }
+
+    fn plan_delete(&self, payload: &SensuPayload) -> Result<PlannedRequest, SensuError> {
+        match self.api {
+            SensuApi::Legacy => Ok(PlannedRequest {
+                method: Method::POST,
+                uri: self.uri(SensuEndpoint::Clear)?,
+                body: Some(payload.clone().into_body(&self.api)?),
             }),
-            (_, _) => {
-                println!("No targets specified - Exiting...");
-                process::exit(1);
+            SensuApi::Go(_) => {
+                let name = payload.entry_id();
+                Ok(PlannedRequest {
+                    method: Method::DELETE,
+                    uri: self.uri(SensuEndpoint::SilencedEntry(&name))?,
+                    body: None,
+                })
             },
-        };
-        Ok(())
+        }
     }
 
-    pub fn list(&mut self, s: ListOpts) -> Result<(), Box<dyn Error>> {
-        let compile_regex = |string: Option<&str>| -> Result<Regex, Box<dyn Error>> {
-            let regex = RegexBuilder::new(string.unwrap_or(".*")).size_limit(8192)
-                .dfa_size_limit(8192).build()?;
-            Ok(regex)
-        };
+    fn create_silence(&mut self, payload: SensuPayload) -> Result<(), SensuError> {
+        let plan = self.plan_create(&payload)?;
+        self.execute(plan)
+    }
 
-        let sub_regex = compile_regex(s.sub.as_ref().map(|s| s.as_str()))?;
-        let chk_regex = compile_regex(s.chk.as_ref().map(|s| s.as_str()))?;
+    fn delete_silence(&mut self, payload: SensuPayload) -> Result<(), SensuError> {
+        let plan = self.plan_delete(&payload)?;
+        self.execute(plan)
+    }
 
-        println!("Active silences:");
+    fn silences(&mut self) -> Result<Vec<Silence>, SensuError> {
         let resp = self.request(Method::GET, SensuEndpoint::Silenced, None)?;
-        if let Some(Value::Array(v)) = resp {
-            if v.len() == 0 {
-                println!("\tNo silences");
-                return Ok(());
-            }
-            for obj in v {
-                if let Value::Object(o) = obj {
-                    let user = o.get("creator").and_then(|c| c.as_str()).unwrap_or("unknown");
-                    let subscription = o.get("subscription").and_then(|c| c.as_str())
-                        .unwrap_or("all");
-                    let check = o.get("check").and_then(|c| c.as_str()).unwrap_or("all");
-                    let expire = o.get("expire").and_then(|c| c.as_u64());
-                    let eor = o.get("expire_on_resolve").and_then(|c| c.as_bool()).unwrap_or(false);
-
-                    if !sub_regex.is_match(subscription) || !chk_regex.is_match(check) {
-                        continue
-                    }
-
-                    println!("\tSubscription:\t\t{}", subscription);
-                    println!("\tCheck:\t\t\t{}", check);
-                    match expire {
-                        Some(num) => println!("\tExpiration:\t\t{}", num),
-                        None => println!("\tExpiration:\t\tnever"),
-                    };
-                    println!("\tExpire on resolve:\t{}", eor);
-                    println!("\tUser:\t\t\t{}", user);
-                    println!();
-                }
-            }
-        }
-        Ok(())
+        self.api.parse_silences(resp)
     }
 }
@@ -1,13 +1,15 @@
-use std::convert::TryInto;
-
 use hyper::Uri;
 
+use super::SensuApi;
+
 /// Enum representing the endpoints in Sensu as a type that shush accesses
 #[derive(Clone)]
 pub enum SensuEndpoint<'a> {
     /// Endpoint for listing silences
     Silenced,
-    /// Endpoint for clearing silences
+    /// Endpoint for a single silence entry by name (Sensu Go only)
+    SilencedEntry(&'a str),
+    /// Endpoint for clearing silences (Sensu 0.29/1.x only)
     Clear,
     /// Endpoint for getting clients
     Clients,
@@ -15,19 +17,49 @@ pub enum SensuEndpoint<'a> {
     Client(&'a str),
     /// Endpoint for getting check results
     Results,
+    /// Endpoint for logging in with user and password (Sensu Go only)
+    Auth,
+    /// Endpoint for refreshing an access token (Sensu Go only)
+    AuthToken,
 }
 
-impl<'a> TryInto<Uri> for SensuEndpoint<'a> {
-    type Error = String;
-
-    fn try_into(self) -> Result<Uri, Self::Error> {
-        match self {
-            SensuEndpoint::Silenced => "/silenced".parse::<Uri>().map_err(|e| format!("{}", e)),
-            SensuEndpoint::Clear => "/silenced/clear".parse::<Uri>().map_err(|e| format!("{}", e)),
-            SensuEndpoint::Client(c) => format!("/clients/{}", c).parse::<Uri>()
-                .map_err(|e| format!("{}", e)),
-            SensuEndpoint::Clients => "/clients".parse::<Uri>().map_err(|e| format!("{}", e)),
-            SensuEndpoint::Results => "/results".parse::<Uri>().map_err(|e| format!("{}", e)),
-        }
+impl<'a> SensuEndpoint<'a> {
+    /// Resolve endpoint to a path for the given API flavor
+    pub fn uri(&self, api: &SensuApi) -> Result<Uri, String> {
+        let path = match (api, self) {
+            (SensuApi::Legacy, SensuEndpoint::Silenced) => "/silenced".to_string(),
+            (SensuApi::Legacy, SensuEndpoint::Clear) => "/silenced/clear".to_string(),
+            (SensuApi::Legacy, SensuEndpoint::Clients) => "/clients".to_string(),
+            (SensuApi::Legacy, SensuEndpoint::Client(c)) => format!("/clients/{}", c),
+            (SensuApi::Legacy, SensuEndpoint::Results) => "/results".to_string(),
+            (SensuApi::Legacy, SensuEndpoint::SilencedEntry(_)) => {
+                return Err("Sensu 1.x does not support addressing silences by name".to_string());
+            },
+            (SensuApi::Legacy, SensuEndpoint::Auth)
+                | (SensuApi::Legacy, SensuEndpoint::AuthToken) => {
+                return Err("Sensu 1.x does not support token authentication".to_string());
+            },
+            (SensuApi::Go(ns), SensuEndpoint::Silenced) => {
+                format!("/api/core/v2/namespaces/{}/silenced", ns)
+            },
+            (SensuApi::Go(ns), SensuEndpoint::SilencedEntry(name)) => {
+                format!("/api/core/v2/namespaces/{}/silenced/{}", ns, name)
+            },
+            (SensuApi::Go(ns), SensuEndpoint::Clients) => {
+                format!("/api/core/v2/namespaces/{}/entities", ns)
+            },
+            (SensuApi::Go(ns), SensuEndpoint::Client(c)) => {
+                format!("/api/core/v2/namespaces/{}/entities/{}", ns, c)
+            },
+            (SensuApi::Go(ns), SensuEndpoint::Results) => {
+                format!("/api/core/v2/namespaces/{}/events", ns)
+            },
+            (SensuApi::Go(_), SensuEndpoint::Auth) => "/auth".to_string(),
+            (SensuApi::Go(_), SensuEndpoint::AuthToken) => "/auth/token".to_string(),
+            (SensuApi::Go(_), SensuEndpoint::Clear) => {
+                return Err("Sensu Go has no clear endpoint - delete silences by name".to_string());
+            },
+        };
+        path.parse::<Uri>().map_err(|e| format!("{}", e))
     }
 }
@@ -1,5 +1,10 @@
 use std::fmt::{self,Display};
 
+use chrono_tz::Tz;
+
+use output::timestamp;
+use time::format_duration;
+
 /// Enum for all types of duration of silences - only used in silences
 #[derive(Debug,PartialEq,Clone)]
 pub enum Expire {
@@ -7,15 +12,30 @@ pub enum Expire {
     NoExpiration(bool),
     /// Expires in `usize` seconds with optional expire on resolve
     Expire(usize, bool),
+    /// Expires at the UNIX timestamp `i64` with optional expire on resolve
+    Until(i64, bool),
+}
+
+impl Expire {
+    /// Describe the expiration with deadlines rendered in the time zone `tz`, or local time
+    /// if `None`
+    pub fn describe(&self, tz: Option<Tz>) -> String {
+        let (when, eor) = match *self {
+            Expire::NoExpiration(true) => return "not expire until resolution".to_string(),
+            Expire::NoExpiration(false) => return "never expire".to_string(),
+            Expire::Expire(sz, eor) => (format!("expire in {}", format_duration(sz as u64)), eor),
+            Expire::Until(ts, eor) => (format!("expire at {}", timestamp(ts, tz)), eor),
+        };
+        if eor {
+            format!("{} or on resolution", when)
+        } else {
+            when
+        }
+    }
 }
 
 impl Display for Expire {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match *self {
-            Expire::NoExpiration(true) => write!(f, "not expire until resolution"),
-            Expire::NoExpiration(false) => write!(f, "never expire"),
-            Expire::Expire(sz, true) => write!(f, "expire in {} seconds or on resolution", sz),
-            Expire::Expire(sz, false) => write!(f, "expire in {} seconds", sz),
-        }
+        write!(f, "{}", self.describe(None))
     }
 }
@@ -0,0 +1,98 @@
+use std::ffi::CStr;
+use std::fmt::Display;
+use std::fs;
+
+use libc;
+use serde_json::{self,Value};
+use serde_yaml;
+
+use err::SensuError;
+use super::SensuApi;
+
+/// Default location of the client definition of the local Sensu client or agent
+pub fn default_client_config(api: &SensuApi) -> &'static str {
+    match *api {
+        SensuApi::Legacy => "/etc/sensu/conf.d/client.json",
+        SensuApi::Go(_) => "/etc/sensu/agent.yml",
+    }
+}
+
+/// Name the local Sensu client registers under, read from `client.name` in the client
+/// definition on Sensu 1.x or from `name` in the agent config on Sensu Go - both fall back to
+/// the host name if it is not set
+pub fn local_client_name(api: &SensuApi, path: Option<String>) -> Result<String, SensuError> {
+    let path = path.unwrap_or_else(|| default_client_config(api).to_string());
+    let contents = fs::read_to_string(&path).map_err(|e| SensuError::new_string(
+        format_args!("Failed to read local Sensu client config {}: {}", path, e)
+    ))?;
+    let parse_err = |e: &dyn Display| SensuError::new_string(
+        format_args!("Failed to parse local Sensu client config {}: {}", path, e)
+    );
+    let name = match *api {
+        SensuApi::Legacy => {
+            let config: Value = serde_json::from_str(&contents).map_err(|e| parse_err(&e))?;
+            match config.pointer("/client/name").and_then(|n| n.as_str()) {
+                Some(n) => n.to_string(),
+                None => hostname()?,
+            }
+        },
+        SensuApi::Go(_) => {
+            // The stock agent config has every option commented out, which is not a valid
+            // YAML document
+            let blank = contents.lines().map(|l| l.trim())
+                .all(|l| l.is_empty() || l.starts_with('#'));
+            let config = if blank {
+                serde_yaml::Value::Null
+            } else {
+                serde_yaml::from_str(&contents).map_err(|e| parse_err(&e))?
+            };
+            match config.get("name").and_then(|n| n.as_str()) {
+                Some(n) => n.to_string(),
+                None => hostname()?,
+            }
+        },
+    };
+    if name.is_empty() {
+        return Err(parse_err(&"client name is empty"));
+    }
+    Ok(name)
+}
+
+fn hostname() -> Result<String, SensuError> {
+    let mut buf = [0 as libc::c_char; 256];
+    if unsafe { libc::gethostname(buf.as_mut_ptr(), buf.len()) } != 0 {
+        return Err(SensuError::new("Failed to look up the host name"));
+    }
+    buf[buf.len() - 1] = 0;
+    Ok(unsafe { CStr::from_ptr(buf.as_ptr()) }.to_string_lossy().into_owned())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::env;
+    use std::process;
+
+    fn name_from(api: &SensuApi, file: &str, contents: &str) -> Result<String, SensuError> {
+        let path = env::temp_dir().join(format!("shush-{}-{}", process::id(), file));
+        fs::write(&path, contents).unwrap();
+        let name = local_client_name(api, Some(path.to_string_lossy().into_owned()));
+        fs::remove_file(&path).unwrap();
+        name
+    }
+
+    #[test]
+    fn test_local_client_name() {
+        let go = SensuApi::Go("default".to_string());
+        assert_eq!(name_from(&SensuApi::Legacy, "client.json",
+                             r#"{"client": {"name": "host1", "address": "10.0.0.1"}}"#).unwrap(),
+                   "host1");
+        assert_eq!(name_from(&SensuApi::Legacy, "noname.json", r#"{"client": {}}"#).unwrap(),
+                   hostname().unwrap());
+        assert_eq!(name_from(&go, "agent.yml", "name: host2\nbackend-url: ws://sensu:8081\n")
+                   .unwrap(), "host2");
+        assert_eq!(name_from(&go, "stock.yml", "---\n# name: \"\"\n").unwrap(),
+                   hostname().unwrap());
+        assert!(local_client_name(&go, Some("/nonexistent/agent.yml".to_string())).is_err());
+    }
+}
@@ -1,5 +1,11 @@
 //! Sensu API related request and response-parsing logic
 
+mod api;
+pub use self::api::*;
+
+mod auth;
+pub use self::auth::*;
+
 mod client;
 pub use self::client::*;
 
@@ -9,8 +15,17 @@ pub use self::endpoint::*;
 mod expire;
 pub use self::expire::*;
 
+mod local;
+pub use self::local::*;
+
+mod models;
+pub use self::models::*;
+
 mod payload;
 pub use self::payload::*;
 
 mod resource;
 pub use self::resource::*;
+
+mod tls;
+pub use self::tls::*;
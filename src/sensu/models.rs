@@ -0,0 +1,392 @@
+use serde::de::DeserializeOwned;
+use serde_json::{self,Map,Value};
+
+use super::SensuApi;
+use err::SensuError;
+
+/// Silence entry as returned by the Sensu API
+#[derive(Clone,Debug,Deserialize,Serialize,PartialEq)]
+pub struct Silence {
+    /// ID of the entry in the form `SUBSCRIPTION:CHECK` with `*` as a wildcard
+    pub id: String,
+    /// Subscription or `client:` target - all targets if `None`
+    #[serde(default)]
+    pub subscription: Option<String>,
+    /// Check name - all checks if `None`
+    #[serde(default)]
+    pub check: Option<String>,
+    /// User who created the entry
+    #[serde(default)]
+    pub creator: Option<String>,
+    /// Reason the entry was created
+    #[serde(default)]
+    pub reason: Option<String>,
+    /// Remaining seconds until the entry expires - no expiration if `None` or negative
+    #[serde(default)]
+    pub expire: Option<i64>,
+    /// Whether the entry is cleared when the check resolves
+    #[serde(default)]
+    pub expire_on_resolve: bool,
+    /// UNIX timestamp at which the entry takes effect
+    #[serde(default)]
+    pub begin: Option<i64>,
+    /// UNIX timestamp at which the entry was created (Sensu 1.x only)
+    #[serde(default)]
+    pub timestamp: Option<i64>,
+    /// UNIX timestamp at which the entry expires (Sensu Go only)
+    #[serde(default)]
+    pub expire_at: Option<i64>,
+}
+
+impl Silence {
+    /// Whether this entry suppresses `check` on `client` at the UNIX timestamp `now`,
+    /// following Sensu's rules: the entry must target the client, one of its subscriptions
+    /// or all subscriptions, target the check or all checks, and be in effect
+    pub fn silences(&self, client: &ClientInfo, check: &str, now: i64) -> bool {
+        let targets_client = match self.subscription {
+            Some(ref sub) => client.subscriptions.contains(sub),
+            None => true,
+        };
+        let targets_check = self.check.as_ref().map(|c| c == check).unwrap_or(true);
+        let begun = self.begin.map(|b| b <= now).unwrap_or(true);
+        let expired = self.expire_at.map(|e| e <= now).unwrap_or(false);
+        targets_client && targets_check && begun && !expired
+    }
+
+    /// UNIX timestamp at which the entry expires given the UNIX timestamp `now` the entry
+    /// was fetched at - never if `None`
+    pub fn expires_at(&self, now: i64) -> Option<i64> {
+        self.expire_at.or_else(|| self.expire.filter(|e| *e >= 0).map(|e| now + e))
+    }
+}
+
+/// Sensu client or Sensu Go entity
+#[derive(Clone,Debug,Deserialize,Serialize,PartialEq)]
+pub struct ClientInfo {
+    /// Client name
+    pub name: String,
+    /// Subscriptions the client belongs to
+    #[serde(default)]
+    pub subscriptions: Vec<String>,
+    /// All other attributes of the client
+    #[serde(flatten)]
+    pub attributes: Map<String, Value>,
+}
+
+impl ClientInfo {
+    /// Look up an attribute of the client by JSON pointer, such as `/ec2/instance_id`
+    pub fn attribute(&self, pointer: &str) -> Option<&Value> {
+        let mut parts = pointer.trim_start_matches('/').split('/');
+        let first = self.attributes.get(parts.next()?)?;
+        parts.try_fold(first, |v, p| v.get(p))
+    }
+
+    /// Look up an attribute by JSON pointer as strings - the elements of an array or the
+    /// attribute itself otherwise, including the client name and subscriptions
+    pub fn attribute_strings(&self, pointer: &str) -> Vec<String> {
+        match pointer {
+            "/name" => vec![self.name.clone()],
+            "/subscriptions" => self.subscriptions.clone(),
+            _ => match self.attribute(pointer) {
+                Some(Value::Array(items)) => items.iter().filter_map(scalar_string).collect(),
+                Some(value) => scalar_string(value).into_iter().collect(),
+                None => Vec::new(),
+            },
+        }
+    }
+}
+
+fn scalar_string(value: &Value) -> Option<String> {
+    match *value {
+        Value::String(ref s) => Some(s.clone()),
+        Value::Number(ref n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+/// Check definition attached to a check result
+#[derive(Clone,Debug,Deserialize,Serialize,PartialEq)]
+pub struct CheckInfo {
+    /// Check name
+    pub name: String,
+    /// Exit status of the last execution
+    #[serde(default)]
+    pub status: Option<i64>,
+    /// Output of the last execution
+    #[serde(default)]
+    pub output: Option<String>,
+    /// All other attributes of the check
+    #[serde(flatten)]
+    pub attributes: Map<String, Value>,
+}
+
+/// Latest result of a check on a client
+#[derive(Clone,Debug,Deserialize,Serialize,PartialEq)]
+pub struct CheckResult {
+    /// Name of the client that ran the check
+    pub client: String,
+    /// Check that was run
+    pub check: CheckInfo,
+}
+
+/// Object metadata used by every Sensu Go resource
+#[derive(Clone,Debug,Deserialize,Serialize,PartialEq)]
+pub struct GoMetadata {
+    /// Resource name
+    pub name: String,
+    /// Namespace of the resource
+    #[serde(default)]
+    pub namespace: Option<String>,
+    /// Labels on the resource
+    #[serde(default)]
+    pub labels: Map<String, Value>,
+    /// Annotations on the resource
+    #[serde(default)]
+    pub annotations: Map<String, Value>,
+}
+
+/// Silence entry as returned by the Sensu Go API
+#[derive(Clone,Debug,Deserialize,Serialize,PartialEq)]
+pub struct GoSilence {
+    /// Metadata holding the name of the entry
+    pub metadata: GoMetadata,
+    /// Subscription or `entity:` target
+    #[serde(default)]
+    pub subscription: Option<String>,
+    /// Check name
+    #[serde(default)]
+    pub check: Option<String>,
+    /// User who created the entry
+    #[serde(default)]
+    pub creator: Option<String>,
+    /// Reason the entry was created
+    #[serde(default)]
+    pub reason: Option<String>,
+    /// Seconds the entry lasts for - no expiration if `None` or negative
+    #[serde(default)]
+    pub expire: Option<i64>,
+    /// Whether the entry is cleared when the check resolves
+    #[serde(default)]
+    pub expire_on_resolve: bool,
+    /// UNIX timestamp at which the entry takes effect
+    #[serde(default)]
+    pub begin: Option<i64>,
+    /// UNIX timestamp at which the entry expires
+    #[serde(default)]
+    pub expire_at: Option<i64>,
+}
+
+impl From<GoSilence> for Silence {
+    fn from(s: GoSilence) -> Self {
+        Silence {
+            id: s.metadata.name,
+            subscription: s.subscription.filter(|sub| !sub.is_empty()),
+            check: s.check.filter(|chk| !chk.is_empty()),
+            creator: s.creator,
+            reason: s.reason,
+            expire: s.expire,
+            expire_on_resolve: s.expire_on_resolve,
+            begin: s.begin.filter(|b| *b > 0),
+            timestamp: None,
+            expire_at: s.expire_at.filter(|e| *e > 0),
+        }
+    }
+}
+
+/// Entity as returned by the Sensu Go API
+#[derive(Clone,Debug,Deserialize,Serialize,PartialEq)]
+pub struct GoEntity {
+    /// Metadata holding the name and labels of the entity
+    pub metadata: GoMetadata,
+    /// Subscriptions the entity belongs to
+    #[serde(default)]
+    pub subscriptions: Vec<String>,
+    /// All other attributes of the entity
+    #[serde(flatten)]
+    pub attributes: Map<String, Value>,
+}
+
+impl From<GoEntity> for ClientInfo {
+    fn from(e: GoEntity) -> Self {
+        let name = e.metadata.name.clone();
+        let mut attributes = e.attributes;
+        if let Ok(metadata) = serde_json::to_value(e.metadata) {
+            attributes.insert("metadata".to_string(), metadata);
+        }
+        ClientInfo { name, subscriptions: e.subscriptions, attributes }
+    }
+}
+
+/// Check as embedded in a Sensu Go event
+#[derive(Clone,Debug,Deserialize,Serialize,PartialEq)]
+pub struct GoCheck {
+    /// Metadata holding the check name
+    pub metadata: GoMetadata,
+    /// Exit status of the last execution
+    #[serde(default)]
+    pub status: Option<i64>,
+    /// Output of the last execution
+    #[serde(default)]
+    pub output: Option<String>,
+    /// All other attributes of the check
+    #[serde(flatten)]
+    pub attributes: Map<String, Value>,
+}
+
+/// Event as returned by the Sensu Go API
+#[derive(Clone,Debug,Deserialize,Serialize,PartialEq)]
+pub struct GoEvent {
+    /// Entity the event belongs to
+    pub entity: GoEntity,
+    /// Check that produced the event - metrics-only events have none
+    #[serde(default)]
+    pub check: Option<GoCheck>,
+}
+
+impl GoEvent {
+    /// Convert to a check result if the event was produced by a check
+    pub fn into_result(self) -> Option<CheckResult> {
+        let client = self.entity.metadata.name;
+        self.check.map(|c| CheckResult {
+            client,
+            check: CheckInfo {
+                name: c.metadata.name,
+                status: c.status,
+                output: c.output,
+                attributes: c.attributes,
+            },
+        })
+    }
+}
+
+/// Deserialize an API response into a model with an error describing any schema mismatch
+pub fn parse_response<T>(what: &str, value: Option<Value>) -> Result<T, SensuError>
+        where T: DeserializeOwned {
+    serde_json::from_value(value.unwrap_or(Value::Null)).map_err(|e| {
+        SensuError::new_string(format_args!("Unexpected format of {} from Sensu API: {}", what, e))
+    })
+}
+
+impl SensuApi {
+    /// Parse response from the silenced endpoint
+    pub fn parse_silences(&self, value: Option<Value>) -> Result<Vec<Silence>, SensuError> {
+        match *self {
+            SensuApi::Legacy => parse_response("silences", value),
+            SensuApi::Go(_) => parse_response::<Vec<GoSilence>>("silences", value)
+                .map(|v| v.into_iter().map(Silence::from).collect()),
+        }
+    }
+
+    /// Parse response from the clients endpoint
+    pub fn parse_clients(&self, value: Option<Value>) -> Result<Vec<ClientInfo>, SensuError> {
+        match *self {
+            SensuApi::Legacy => parse_response("clients", value),
+            SensuApi::Go(_) => parse_response::<Vec<GoEntity>>("entities", value)
+                .map(|v| v.into_iter().map(ClientInfo::from).collect()),
+        }
+    }
+
+    /// Parse response from the single client endpoint
+    pub fn parse_client(&self, value: Option<Value>) -> Result<ClientInfo, SensuError> {
+        match *self {
+            SensuApi::Legacy => parse_response("client", value),
+            SensuApi::Go(_) => parse_response::<GoEntity>("entity", value).map(ClientInfo::from),
+        }
+    }
+
+    /// Parse response from the results endpoint
+    pub fn parse_results(&self, value: Option<Value>) -> Result<Vec<CheckResult>, SensuError> {
+        match *self {
+            SensuApi::Legacy => parse_response("check results", value),
+            SensuApi::Go(_) => parse_response::<Vec<GoEvent>>("events", value)
+                .map(|v| v.into_iter().filter_map(GoEvent::into_result).collect()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_legacy() {
+        let api = SensuApi::Legacy;
+        let silences = api.parse_silences(serde_json::from_str(r#"[{
+            "id": "client:host1:*", "subscription": "client:host1", "expire": -1,
+            "expire_on_resolve": false, "creator": "jbaublitz", "reason": "reboot",
+            "timestamp": 1500000000, "begin": null
+        }]"#).unwrap()).unwrap();
+        assert_eq!(silences[0].id, "client:host1:*");
+        assert_eq!(silences[0].check, None);
+        assert_eq!(silences[0].reason, Some("reboot".to_string()));
+        assert_eq!(silences[0].timestamp, Some(1500000000));
+
+        let clients = api.parse_clients(serde_json::from_str(r#"[{
+            "name": "host1", "subscriptions": ["linux"], "instance_id": "i-abc"
+        }]"#).unwrap()).unwrap();
+        assert_eq!(clients[0].subscriptions, vec!["linux".to_string()]);
+        assert_eq!(clients[0].attributes.get("instance_id"), Some(&Value::from("i-abc")));
+
+        let results = api.parse_results(serde_json::from_str(r#"[{
+            "client": "host1", "check": {"name": "check_disk", "status": 2, "interval": 60}
+        }]"#).unwrap()).unwrap();
+        assert_eq!(results[0].check.name, "check_disk");
+        assert_eq!(results[0].check.status, Some(2));
+    }
+
+    #[test]
+    fn test_parse_go() {
+        let api = SensuApi::Go("default".to_string());
+        let silences = api.parse_silences(serde_json::from_str(r#"[{
+            "metadata": {"name": "linux:check_disk", "namespace": "default"},
+            "subscription": "linux", "check": "check_disk", "expire": 3600,
+            "expire_on_resolve": true, "creator": "admin", "begin": 1500000000,
+            "expire_at": 1500003600
+        }]"#).unwrap()).unwrap();
+        assert_eq!(silences[0].id, "linux:check_disk");
+        assert_eq!(silences[0].expire_at, Some(1500003600));
+        assert!(silences[0].expire_on_resolve);
+
+        let clients = api.parse_clients(serde_json::from_str(r#"[{
+            "metadata": {"name": "host1", "labels": {"instance_id": "i-abc"}},
+            "subscriptions": ["linux", "entity:host1"], "entity_class": "agent"
+        }]"#).unwrap()).unwrap();
+        assert_eq!(clients[0].name, "host1");
+        assert_eq!(clients[0].attribute(api.instance_id_pointer()), Some(&Value::from("i-abc")));
+
+        let results = api.parse_results(serde_json::from_str(r#"[{
+            "entity": {"metadata": {"name": "host1"}},
+            "check": {"metadata": {"name": "check_disk"}, "status": 0}
+        }]"#).unwrap()).unwrap();
+        assert_eq!(results[0].client, "host1");
+        assert_eq!(results[0].check.name, "check_disk");
+    }
+
+    #[test]
+    fn test_silences() {
+        let client: ClientInfo = serde_json::from_str(
+            r#"{"name": "host1", "subscriptions": ["linux", "client:host1"]}"#
+        ).unwrap();
+        let silence = |json: &str| serde_json::from_str::<Silence>(json).unwrap();
+        assert!(silence(r#"{"id": "linux:*", "subscription": "linux"}"#)
+                .silences(&client, "check_disk", 1000));
+        assert!(silence(r#"{"id": "*:check_disk", "check": "check_disk"}"#)
+                .silences(&client, "check_disk", 1000));
+        assert!(!silence(r#"{"id": "*:check_load", "check": "check_load"}"#)
+                .silences(&client, "check_disk", 1000));
+        assert!(!silence(r#"{"id": "web:*", "subscription": "web"}"#)
+                .silences(&client, "check_disk", 1000));
+        assert!(!silence(r#"{"id": "client:host1:*", "subscription": "client:host1", "begin": 2000}"#)
+                .silences(&client, "check_disk", 1000));
+        assert!(!silence(r#"{"id": "linux:*", "subscription": "linux", "expire_at": 500}"#)
+                .silences(&client, "check_disk", 1000));
+    }
+
+    #[test]
+    fn test_parse_mismatch() {
+        let err = SensuApi::Legacy.parse_silences(serde_json::from_str(r#"[{"check": 5}]"#)
+                                                  .unwrap()).unwrap_err();
+        assert!(err.to_string().starts_with("Unexpected format of silences from Sensu API"));
+    }
+}
@@ -1,11 +1,19 @@
 use std::env;
+use std::fmt::{self,Display};
+use std::time::{SystemTime,UNIX_EPOCH};
 
 use serde_json::{Value,Map,Number};
 
-use super::Expire;
+use err::SensuError;
+use super::{silenced_name,Expire,SensuApi};
+
+/// User recorded as the creator of silences - `$USER` or `shush` if it is not set
+pub fn current_user() -> String {
+    env::var("USER").unwrap_or_else(|_| "shush".to_string())
+}
 
 /// Generic struct for any Sensu payload - can be used for clear or silence
-#[derive(Debug)]
+#[derive(Clone,Debug,Default,PartialEq)]
 pub struct SensuPayload {
     /// Resource (node, client, or subscription)
     pub res: Option<String>,
@@ -13,15 +21,46 @@ pub struct SensuPayload {
     pub chk: Option<String>,
     /// Time until expiration
     pub expire: Option<Expire>,
+    /// Why the silence was created
+    pub reason: Option<String>,
+    /// User recorded as the creator - `current_user()` if `None`
+    pub creator: Option<String>,
+    /// UNIX timestamp at which the silence takes effect - immediately if `None`
+    pub begin: Option<i64>,
+    /// ID of an existing silence entry - takes precedence over all other fields when clearing
+    pub id: Option<String>,
 }
 
-impl Into<Map<String, Value>> for SensuPayload {
-    fn into(self) -> Map<String, Value> {
+impl SensuPayload {
+    /// ID of the silence entry this payload refers to
+    pub fn entry_id(&self) -> String {
+        self.id.clone().unwrap_or_else(|| silenced_name(self.res.as_deref(), self.chk.as_deref()))
+    }
+
+    /// Convert to `Map` for HTTP body in the format the given API flavor expects
+    pub fn into_body(self, api: &SensuApi) -> Result<Map<String, Value>, SensuError> {
         let mut payload = Map::new();
 
-        // Always inject USER information into payload as creator field
-        let user = env::var("USER").unwrap_or("shush".to_string());
-        payload.insert("creator".to_string(), Value::String(user));
+        // Entries cleared by ID need nothing else to identify them
+        if let Some(id) = self.id {
+            payload.insert("id".to_string(), Value::String(id));
+            return Ok(payload);
+        }
+
+        // Sensu Go identifies silences by name and namespace in the metadata object
+        if let SensuApi::Go(ref ns) = *api {
+            let mut metadata = Map::new();
+            metadata.insert("name".to_string(), Value::String(
+                silenced_name(self.res.as_deref(), self.chk.as_deref())
+            ));
+            metadata.insert("namespace".to_string(), Value::String(ns.clone()));
+            payload.insert("metadata".to_string(), Value::Object(metadata));
+        }
+
+        // Inject USER information into payload as creator field unless the payload recreates
+        // an entry on behalf of its original creator
+        let creator = self.creator.unwrap_or_else(current_user);
+        payload.insert("creator".to_string(), Value::String(creator));
 
         // Handle subscription for payload as Sensu client value, subscription, or all
         if let Some(string) = self.res {
@@ -33,19 +72,57 @@ impl Into<Map<String, Value>> for SensuPayload {
             payload.insert("check".to_string(), Value::String(c));
         }
 
+        if let Some(r) = self.reason {
+            payload.insert("reason".to_string(), Value::String(r));
+        }
+
+        if let Some(b) = self.begin {
+            payload.insert("begin".to_string(), Value::Number(Number::from(b)));
+        }
+
+        // Sensu Go counts the expiration from the begin time but Sensu 1.x counts it from
+        // creation, so durations are converted relative to whichever applies at submit time
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64)
+            .unwrap_or(0);
+        let start = match *api {
+            SensuApi::Legacy => now,
+            SensuApi::Go(_) => self.begin.unwrap_or(now),
+        };
+        let delay = self.begin.map(|b| (b - start).max(0) as usize).unwrap_or(0);
+
         // Handle silence duration
-        if let Some(Expire::NoExpiration(eor)) = self.expire {
-            if eor {
-                payload.insert("expire_on_resolve".to_string(), Value::Bool(true));
-            }
-        } else if let Some(Expire::Expire(num, eor)) = self.expire {
+        let (expire, eor) = match self.expire {
+            Some(Expire::NoExpiration(eor)) => (None, eor),
+            // Both APIs take the expiration as a signed 64 bit number
+            Some(Expire::Expire(num, eor)) => (Some(num.checked_add(delay)
+                .filter(|n| *n <= i64::MAX as usize).ok_or_else(|| SensuError::new_string(
+                    format_args!("Expiration of {} seconds is too long", num)
+                ))?), eor),
+            // Never let a deadline that has passed turn into an entry without expiration
+            Some(Expire::Until(ts, eor)) => (Some((ts - start).max(1) as usize), eor),
+            None => (None, false),
+        };
+        if let Some(num) = expire {
             payload.insert("expire".to_string(), Value::Number(Number::from(num)));
-            if eor {
-                payload.insert("expire_on_resolve".to_string(), Value::Bool(true));
-            }
+        }
+        if eor {
+            payload.insert("expire_on_resolve".to_string(), Value::Bool(true));
         }
 
-        // Convert to `Map` for HTTP body
-        payload
+        Ok(payload)
+    }
+}
+
+impl Display for SensuPayload {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(ref id) = self.id {
+            return write!(f, "silence entry {}", id);
+        }
+        match (&self.res, &self.chk) {
+            (Some(r), Some(c)) => write!(f, "check {} on resource {}", c, r),
+            (Some(r), None) => write!(f, "all checks on resource {}", r),
+            (None, Some(c)) => write!(f, "check {} on all resources", c),
+            (None, None) => write!(f, "all checks on all resources"),
+        }
     }
 }
@@ -2,13 +2,27 @@ use std::borrow::Borrow;
 use std::fmt::{self,Display};
 use std::hash::{Hash,Hasher};
 
+use super::SensuApi;
+
 /// Sensu resource for conversion to payload
 #[derive(Clone,Debug,Eq,PartialEq)]
 pub enum SensuResource {
+    /// Sensu client by name
     Client(String),
+    /// Sensu subscription
     Subscription(String),
 }
 
+impl SensuResource {
+    /// Subscription string representing this resource for the given API flavor
+    pub fn subscription(&self, api: &SensuApi) -> String {
+        match *self {
+            SensuResource::Client(ref s) => format!("{}{}", api.client_prefix(), s),
+            SensuResource::Subscription(ref s) => s.clone(),
+        }
+    }
+}
+
 impl Display for SensuResource {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
@@ -0,0 +1,86 @@
+use std::fs;
+
+use hyper::client::HttpConnector;
+use hyper_tls::HttpsConnector;
+use native_tls::{Certificate,Identity,TlsConnector};
+
+use err::SensuError;
+
+/// TLS settings for connecting to the Sensu API over HTTPS
+#[derive(Clone,Debug,Default,PartialEq)]
+pub struct TlsConfig {
+    /// Path to a PEM bundle of additional trusted CA certificates
+    pub ca_bundle: Option<String>,
+    /// Path to a PEM client certificate for mutual TLS
+    pub client_cert: Option<String>,
+    /// Path to the PEM PKCS #8 private key for the client certificate
+    pub client_key: Option<String>,
+    /// Skip server certificate and hostname verification
+    pub insecure_skip_verify: bool,
+}
+
+fn read_file(path: &str) -> Result<Vec<u8>, SensuError> {
+    fs::read(path).map_err(|e| SensuError::new_string(format_args!("Failed to read {}: {}", path, e)))
+}
+
+impl TlsConfig {
+    /// Build a connector that speaks both HTTP and HTTPS using these settings
+    pub fn connector(&self) -> Result<HttpsConnector<HttpConnector>, SensuError> {
+        let mut builder = TlsConnector::builder();
+        if let Some(ref path) = self.ca_bundle {
+            let pem = read_file(path)?;
+            let certs = pem_blocks(&pem);
+            if certs.is_empty() {
+                return Err(SensuError::new_string(format_args!("No certificates found in {}", path)));
+            }
+            for cert in certs {
+                builder.add_root_certificate(Certificate::from_pem(cert.as_bytes())
+                                             .map_err(SensuError::new_string)?);
+            }
+        }
+        match (&self.client_cert, &self.client_key) {
+            (Some(cert), Some(key)) => {
+                let identity = Identity::from_pkcs8(&read_file(cert)?, &read_file(key)?)
+                    .map_err(SensuError::new_string)?;
+                builder.identity(identity);
+            },
+            (None, None) => (),
+            (_, _) => {
+                return Err(SensuError::new("client_cert and client_key must be configured together"));
+            },
+        };
+        if self.insecure_skip_verify {
+            builder.danger_accept_invalid_certs(true).danger_accept_invalid_hostnames(true);
+        }
+
+        let mut http = HttpConnector::new(4);
+        http.enforce_http(false);
+        Ok(HttpsConnector::from((http, builder.build().map_err(SensuError::new_string)?)))
+    }
+}
+
+/// Split a PEM bundle into the individual certificates it contains
+fn pem_blocks(pem: &[u8]) -> Vec<String> {
+    let text = String::from_utf8_lossy(pem);
+    let end = "-----END CERTIFICATE-----";
+    text.split_inclusive(end).filter_map(|block| {
+        block.find("-----BEGIN CERTIFICATE-----")
+            .filter(|_| block.ends_with(end))
+            .map(|start| block[start..].to_string())
+    }).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::pem_blocks;
+
+    #[test]
+    fn test_pem_blocks() {
+        let bundle = "# Root CA\n-----BEGIN CERTIFICATE-----\nAAAA\n-----END CERTIFICATE-----\n\
+                      -----BEGIN CERTIFICATE-----\nBBBB\n-----END CERTIFICATE-----\ntrailing";
+        assert_eq!(pem_blocks(bundle.as_bytes()), vec![
+            "-----BEGIN CERTIFICATE-----\nAAAA\n-----END CERTIFICATE-----".to_string(),
+            "-----BEGIN CERTIFICATE-----\nBBBB\n-----END CERTIFICATE-----".to_string(),
+        ]);
+    }
+}
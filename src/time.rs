@@ -0,0 +1,295 @@
+//! Parsing of durations and calendar times given on the command line
+
+use std::str::FromStr;
+
+use chrono::{DateTime,Datelike,Duration,NaiveDate,NaiveDateTime,NaiveTime,TimeZone,Weekday};
+use nom::IResult;
+use nom::branch::alt;
+use nom::bytes::complete::tag_no_case;
+use nom::character::complete::{char,digit1,space0};
+use nom::combinator::{map_res,opt,recognize,value};
+use nom::error::ErrorKind;
+use nom::sequence::{pair,preceded};
+
+use err::SensuError;
+
+/// Parse a number with an optional fractional part such as `90`, `1.5` or `.5`
+fn number(i: &str) -> IResult<&str, f64> {
+    map_res(alt((
+        recognize(pair(digit1, opt(pair(char('.'), digit1)))),
+        recognize(pair(char('.'), digit1)),
+    )), |n: &str| n.parse::<f64>())(i)
+}
+
+/// Parse a unit into its length in seconds - longer names are tried first so that `min` is
+/// not read as `m` followed by garbage
+fn unit(i: &str) -> IResult<&str, f64> {
+    alt((
+        value(60. * 60. * 24. * 7., alt((tag_no_case("weeks"), tag_no_case("week"),
+                                         tag_no_case("wks"), tag_no_case("wk"),
+                                         tag_no_case("w")))),
+        value(60. * 60. * 24., alt((tag_no_case("days"), tag_no_case("day"), tag_no_case("d")))),
+        value(60. * 60., alt((tag_no_case("hours"), tag_no_case("hour"), tag_no_case("hrs"),
+                              tag_no_case("hr"), tag_no_case("h")))),
+        value(60., alt((tag_no_case("minutes"), tag_no_case("minute"), tag_no_case("mins"),
+                        tag_no_case("min"), tag_no_case("m")))),
+        value(1., alt((tag_no_case("seconds"), tag_no_case("second"), tag_no_case("secs"),
+                       tag_no_case("sec"), tag_no_case("s")))),
+    ))(i)
+}
+
+/// Parse a single segment such as `1h`, `1.5 hours` or `30` - a number without a unit is
+/// a number of seconds
+fn segment(i: &str) -> IResult<&str, f64> {
+    let (i, num) = number(i)?;
+    let (i, unit) = opt(preceded(space0, unit))(i)?;
+    Ok((i, num * unit.unwrap_or(1.)))
+}
+
+/// Parse duration such as `1h30m`, `1.5d`, `2 weeks` or `90 minutes` into seconds, rounding
+/// to the nearest second
+pub fn parse_duration(duration: &str) -> Result<usize, SensuError> {
+    let invalid = |rest: &str| {
+        let offset = duration.len() - rest.len();
+        let position = duration[..offset].chars().count();
+        let found = match rest.chars().next() {
+            Some(c) => format!("unexpected \"{}\" at position {}", c, position + 1),
+            None => "unexpected end of input".to_string(),
+        };
+        SensuError::new_string(format_args!(
+            "Invalid duration \"{}\" - {}\n\t{}\n\t{}^\nExpected a number of seconds or numbers \
+             followed by weeks, days, hours, minutes or seconds such as 1d2h30m or 90 minutes",
+            duration, found, duration, " ".repeat(position)
+        ))
+    };
+
+    let mut seconds = 0.;
+    let mut rest = duration;
+    loop {
+        let (i, _) = space0::<_, (&str, ErrorKind)>(rest).map_err(|_| invalid(rest))?;
+        if i.is_empty() {
+            if rest.len() == duration.len() {
+                return Err(invalid(i));
+            }
+            break;
+        }
+        let (i, secs) = segment(i).map_err(|_| invalid(i))?;
+        seconds += secs;
+        rest = i;
+    }
+    let seconds = seconds.round();
+    // i64::MAX is not representable and rounds up to 2^63
+    if !seconds.is_finite() || seconds >= i64::MAX as f64 {
+        return Err(SensuError::new_string(format_args!("Duration \"{}\" is too long", duration)));
+    }
+    Ok(seconds as usize)
+}
+
+/// Format a number of seconds in human units such as `1h 12m` or `3d 4h`, keeping the two
+/// most significant units
+pub fn format_duration(secs: u64) -> String {
+    let units = [(secs / 86400, "d"), (secs / 3600 % 24, "h"), (secs / 60 % 60, "m"),
+                 (secs % 60, "s")];
+    let parts: Vec<String> = units.iter().skip_while(|&&(n, _)| n == 0).take(2)
+        .filter(|&&(n, _)| n > 0).map(|&(n, u)| format!("{}{}", n, u)).collect();
+    if parts.is_empty() {
+        "0s".to_string()
+    } else {
+        parts.join(" ")
+    }
+}
+
+/// Resolve a date and optional wall-clock time in the time zone of `now`, failing for a time
+/// that a daylight saving change skips
+fn at<T>(now: &DateTime<T>, date: NaiveDate, time: Option<NaiveTime>)
+         -> Result<DateTime<T>, SensuError> where T: TimeZone {
+    // Midnight if no time is given
+    let local = date.and_time(time.unwrap_or_default());
+    now.timezone().from_local_datetime(&local).earliest().ok_or_else(|| {
+        SensuError::new_string(format_args!(
+            "Time {} does not exist in the time zone in use because the clocks skip it for \
+             daylight saving time", local.format("%Y-%m-%d %H:%M")
+        ))
+    })
+}
+
+/// Parse a time of day such as `17:30` or `17:30:15`
+fn parse_time_of_day(s: &str) -> Option<NaiveTime> {
+    NaiveTime::parse_from_str(s, "%H:%M").or_else(|_| NaiveTime::parse_from_str(s, "%H:%M:%S"))
+        .ok()
+}
+
+/// Parse a calendar time into a UNIX timestamp. Accepts UNIX timestamps written as `@<epoch>`,
+/// ISO 8601 timestamps
+/// with an offset, dates and times without an offset in the time zone of `now`, wall-clock
+/// times such as `17:30`, and `today`, `tomorrow` or a weekday optionally followed by a
+/// wall-clock time. Wall-clock times and weekdays resolve to their next occurrence.
+pub fn parse_time<T>(time: &str, now: &DateTime<T>) -> Result<i64, SensuError>
+        where T: TimeZone {
+    let time = time.trim();
+    let invalid = || SensuError::new_string(format_args!(
+        "Could not parse time \"{}\" - expected @UNIX_TIMESTAMP, an ISO 8601 timestamp, \
+         YYYY-MM-DD [HH:MM[:SS]], HH:MM, or today, tomorrow or a weekday optionally \
+         followed by HH:MM", time
+    ));
+
+    if let Some(epoch) = time.strip_prefix('@') {
+        return epoch.parse::<i64>().map_err(|_| invalid());
+    }
+    // A bare number is as likely meant as a time of day as a UNIX timestamp
+    if time.parse::<i64>().is_ok() {
+        return Err(SensuError::new_string(format_args!(
+            "Time \"{}\" is ambiguous - write a time of day as HH:MM or a UNIX timestamp as \
+             @{}", time, time
+        )));
+    }
+    if let Ok(dt) = DateTime::parse_from_rfc3339(time)
+            .or_else(|_| DateTime::parse_from_str(time, "%Y-%m-%dT%H:%M:%S%z")) {
+        return Ok(dt.timestamp());
+    }
+    let naive = ["%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M", "%Y-%m-%dT%H:%M:%S", "%Y-%m-%dT%H:%M"]
+        .iter().filter_map(|f| NaiveDateTime::parse_from_str(time, f).ok()).next();
+    if let Some(ndt) = naive {
+        return at(now, ndt.date(), Some(ndt.time())).map(|dt| dt.timestamp());
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(time, "%Y-%m-%d") {
+        return at(now, date, None).map(|dt| dt.timestamp());
+    }
+
+    let words: Vec<String> = time.split_whitespace().map(|w| w.to_lowercase()).collect();
+    let (day, time_of_day) = match words.len() {
+        1 => match parse_time_of_day(&words[0]) {
+            Some(t) => (None, Some(t)),
+            None => (Some(words[0].as_str()), None),
+        },
+        2 => (Some(words[0].as_str()), Some(parse_time_of_day(&words[1]).ok_or_else(invalid)?)),
+        _ => return Err(invalid()),
+    };
+
+    let today = now.naive_local().date();
+    let resolved = match day {
+        None => at(now, today, time_of_day).and_then(|dt| if dt <= *now {
+            at(now, today + Duration::days(1), time_of_day)
+        } else {
+            Ok(dt)
+        }),
+        Some("today") if time_of_day.is_some() => at(now, today, time_of_day),
+        Some("tomorrow") => at(now, today + Duration::days(1), time_of_day),
+        Some(d) => {
+            let weekday = Weekday::from_str(d).map_err(|_| invalid())?;
+            let days_ahead = (7 + weekday.num_days_from_monday() as i64
+                              - today.weekday().num_days_from_monday() as i64) % 7;
+            at(now, today + Duration::days(days_ahead), time_of_day).and_then(|dt| {
+                if dt <= *now {
+                    at(now, today + Duration::days(days_ahead + 7), time_of_day)
+                } else {
+                    Ok(dt)
+                }
+            })
+        },
+    };
+    resolved.map(|dt| dt.timestamp())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::Utc;
+    use chrono_tz::Europe::Berlin;
+    use proptest::prelude::*;
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("1h30m").unwrap(), 5400);
+        assert_eq!(parse_duration("90").unwrap(), 90);
+        assert_eq!(parse_duration("1d1s").unwrap(), 86401);
+        assert_eq!(parse_duration("2w").unwrap(), 2 * 7 * 86400);
+        assert_eq!(parse_duration("1.5h").unwrap(), 5400);
+        assert_eq!(parse_duration(".5m").unwrap(), 30);
+        assert_eq!(parse_duration("90 minutes").unwrap(), 5400);
+        assert_eq!(parse_duration(" 1 Hour 30 mins ").unwrap(), 5400);
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("  ").is_err());
+        assert!(parse_duration("1e400d").is_err());
+        assert!(parse_duration(&i64::MAX.to_string()).is_err());
+    }
+
+    #[test]
+    fn test_parse_duration_error_position() {
+        let err = parse_duration("1h 2x").unwrap_err().to_string();
+        assert!(err.starts_with("Invalid duration \"1h 2x\" - unexpected \"x\" at position 5"),
+                "{}", err);
+        assert!(err.contains("\n\t1h 2x\n\t    ^\n"), "{}", err);
+        let err = parse_duration("abc").unwrap_err().to_string();
+        assert!(err.contains("unexpected \"a\" at position 1"), "{}", err);
+        let err = parse_duration("1.h").unwrap_err().to_string();
+        assert!(err.contains("unexpected \".\" at position 2"), "{}", err);
+    }
+
+    proptest! {
+        #[test]
+        fn prop_parse_duration_sums_units(w in 0usize..100, d in 0usize..100, h in 0usize..100,
+                                          m in 0usize..100, s in 0usize..100,
+                                          long in any::<bool>(), sep in "[ \t]{0,2}") {
+            let input = if long {
+                format!("{}{sep}weeks{sep}{}{sep}days{sep}{}{sep}hours{sep}{}{sep}minutes\
+                         {sep}{}{sep}seconds", w, d, h, m, s, sep = sep)
+            } else {
+                format!("{}w{sep}{}d{sep}{}h{sep}{}m{sep}{}s", w, d, h, m, s, sep = sep)
+            };
+            prop_assert_eq!(parse_duration(&input).unwrap(),
+                            (((w * 7 + d) * 24 + h) * 60 + m) * 60 + s);
+        }
+
+        #[test]
+        fn prop_parse_duration_fractions(whole in 0usize..10000, frac in 0usize..10) {
+            let expected = ((whole as f64 + frac as f64 / 10.) * 60.).round() as usize;
+            prop_assert_eq!(parse_duration(&format!("{}.{}m", whole, frac)).unwrap(), expected);
+        }
+
+        #[test]
+        fn prop_parse_duration_rejects_garbage(valid in "[0-9]{1,4}[wdhms]", bad in "[^0-9a-zA-Z. \t]") {
+            let input = format!("{}{}", valid, bad);
+            let err = parse_duration(&input).unwrap_err().to_string();
+            let expected = format!("at position {}", valid.chars().count() + 1);
+            prop_assert!(err.contains(&expected), "{}", err);
+        }
+
+        #[test]
+        fn prop_parse_duration_never_panics(input in "\\PC*") {
+            let _ = parse_duration(&input);
+        }
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(0), "0s");
+        assert_eq!(format_duration(45), "45s");
+        assert_eq!(format_duration(4320), "1h 12m");
+        assert_eq!(format_duration(7200), "2h");
+        assert_eq!(format_duration(90061), "1d 1h");
+        assert_eq!(format_duration(86460), "1d");
+    }
+
+    #[test]
+    fn test_parse_time() {
+        // Friday 2017-07-14 02:40:00 UTC
+        let now = Utc.timestamp_opt(1500000000, 0).unwrap();
+        assert_eq!(parse_time("2017-07-14T12:00:00+02:00", &now).unwrap(), 1500026400);
+        assert_eq!(parse_time("2017-07-14 17:30", &now).unwrap(), 1500053400);
+        assert_eq!(parse_time("17:30", &now).unwrap(), 1500053400);
+        assert_eq!(parse_time("01:00", &now).unwrap(), 1500080400);
+        assert_eq!(parse_time("tomorrow 09:00", &now).unwrap(), 1500109200);
+        assert_eq!(parse_time("Monday", &now).unwrap(), 1500249600);
+        assert_eq!(parse_time("fri 09:00", &now).unwrap(), 1500022800);
+        assert_eq!(parse_time("fri 01:00", &now).unwrap(), 1500080400 + 6 * 86400);
+        assert!(parse_time("someday", &now).is_err());
+        assert_eq!(parse_time("@1600000000", &now).unwrap(), 1600000000);
+        assert!(parse_time("@soon", &now).is_err());
+        assert!(parse_time("1700", &now).unwrap_err().to_string().contains("ambiguous"));
+
+        let berlin = Berlin.timestamp_opt(1490400000, 0).unwrap();
+        let err = parse_time("2017-03-26 02:30", &berlin).unwrap_err().to_string();
+        assert!(err.contains("daylight saving"), "{}", err);
+    }
+}